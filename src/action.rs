@@ -0,0 +1,235 @@
+// Semantic actions. The code of a `{ ... }` block is split into plain C text
+// and references to semantic values and locations: $$, $1, $-1, @$, @2,
+// $<type>3, $name and $[name]. Like Bison, references inside C strings,
+// character literals and comments are left alone.
+use crate::token::Spanned;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Action {
+    // The code as written, including the braces
+    pub code: Spanned<String>,
+    // The code split into text and references. Concatenated they give back
+    // the code, and their spans are positions in the same source.
+    pub fragments: Vec<Fragment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind"))]
+pub enum Fragment {
+    Text(Spanned<String>),
+    Reference(Spanned<Reference>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReferenceKind {
+    // $..., the semantic value
+    Value,
+    // @..., the location
+    Location,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "value"))]
+pub enum Target {
+    // $$ or @$, the value of the rule itself
+    Result,
+    // $1 is the first symbol of the alternative, $0 and below reach into the
+    // symbols before the rule on the stack
+    Index(i32),
+    // $n or $-n whose number does not fit an i32, as written, which is an
+    // error like an index past the end of the alternative
+    OutOfRange(String),
+    // $expr
+    Name(String),
+    // $[expr], which also allows dots and dashes in the name
+    BracketedName(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Reference {
+    pub kind: ReferenceKind,
+    // The explicit type of $<type>n, including the angle brackets
+    pub type_tag: Option<String>,
+    pub target: Target,
+}
+
+impl Action {
+    pub fn new(code: Spanned<String>) -> Self {
+        let fragments = fragments(&code.data, code.span.start);
+        Action { code, fragments }
+    }
+
+    pub fn references(&self) -> impl Iterator<Item = &Spanned<Reference>> {
+        self.fragments.iter().filter_map(|fragment| match fragment {
+            Fragment::Reference(reference) => Some(reference),
+            Fragment::Text(_) => None,
+        })
+    }
+
+    // The code with every reference for which `replace` returns some text
+    // replaced by it, e.g. to turn $1 into yyvsp[-2]. Other references are
+    // kept as written, e.g. $01 stays $01.
+    pub fn rewrite(&self, mut replace: impl FnMut(&Reference) -> Option<String>) -> String {
+        let mut out = String::new();
+        for fragment in &self.fragments {
+            match fragment {
+                Fragment::Text(text) => out.push_str(&text.data),
+                Fragment::Reference(reference) => match replace(&reference.data) {
+                    Some(replacement) => out.push_str(&replacement),
+                    None => out.push_str(self.text(&reference.span)),
+                },
+            }
+        }
+        out
+    }
+
+    // The code of a fragment as written
+    fn text(&self, span: &std::ops::Range<usize>) -> &str {
+        let start = self.code.span.start;
+        &self.code.data[span.start - start..span.end - start]
+    }
+}
+
+// Splits code starting at byte `offset` of the source into fragments. Only
+// ASCII characters are significant, so the code is scanned bytewise.
+fn fragments(code: &str, offset: usize) -> Vec<Fragment> {
+    let bytes = code.as_bytes();
+    let mut fragments = Vec::new();
+    let mut text_start = 0;
+    let mut pos = 0;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\'' if is_lifetime(bytes, pos + 1) => pos += 1,
+            quote @ (b'"' | b'\'') => pos = skip_literal(bytes, pos + 1, quote),
+            b'/' if bytes.get(pos + 1) == Some(&b'*') => {
+                pos = match code[pos + 2..].find("*/") {
+                    Some(end) => pos + 2 + end + 2,
+                    None => bytes.len(),
+                }
+            }
+            b'/' if bytes.get(pos + 1) == Some(&b'/') => {
+                pos = code[pos..].find('\n').map_or(bytes.len(), |end| pos + end)
+            }
+            b'$' | b'@' => match reference(code, pos) {
+                Some((reference, end)) => {
+                    if text_start < pos {
+                        fragments.push(Fragment::Text(Spanned::new(
+                            code[text_start..pos].to_string(),
+                            offset + text_start..offset + pos,
+                        )));
+                    }
+                    fragments.push(Fragment::Reference(Spanned::new(
+                        reference,
+                        offset + pos..offset + end,
+                    )));
+                    text_start = end;
+                    pos = end;
+                }
+                None => pos += 1,
+            },
+            _ => pos += 1,
+        }
+    }
+    if text_start < bytes.len() {
+        fragments.push(Fragment::Text(Spanned::new(
+            code[text_start..].to_string(),
+            offset + text_start..offset + bytes.len(),
+        )));
+    }
+    fragments
+}
+
+// The position after a string or character literal whose body starts at
+// `pos`. Like the lexer, a newline ends a stray quote.
+fn skip_literal(bytes: &[u8], mut pos: usize, quote: u8) -> usize {
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'\\' => pos += 2,
+            b'\n' => return pos,
+            c if c == quote => return pos + 1,
+            _ => pos += 1,
+        }
+    }
+    bytes.len()
+}
+
+// Whether the quote before `pos` starts a Rust lifetime like 'a rather than
+// a character literal, as the lexer decides
+fn is_lifetime(bytes: &[u8], pos: usize) -> bool {
+    bytes.get(pos).is_some_and(|c| is_name_start(*c))
+        && bytes.get(scan(bytes, pos, is_name)) != Some(&b'\'')
+}
+
+fn is_name_start(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'_'
+}
+
+fn is_name(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+// The end of a run of bytes matching `accept`
+fn scan(bytes: &[u8], pos: usize, accept: impl Fn(u8) -> bool) -> usize {
+    pos + bytes[pos..].iter().take_while(|c| accept(**c)).count()
+}
+
+// Parses the reference starting with the '$' or '@' at `start`, returning it
+// and its end. A '$' or '@' which does not start a reference is plain text.
+fn reference(code: &str, start: usize) -> Option<(Reference, usize)> {
+    let bytes = code.as_bytes();
+    let kind = match bytes[start] {
+        b'$' => ReferenceKind::Value,
+        _ => ReferenceKind::Location,
+    };
+    let mut pos = start + 1;
+    let mut type_tag = None;
+    if kind == ReferenceKind::Value && bytes.get(pos) == Some(&b'<') {
+        let end = pos + code[pos..].find(['>', '\n'])?;
+        if bytes[end] != b'>' {
+            return None;
+        }
+        type_tag = Some(code[pos..=end].to_string());
+        pos = end + 1;
+    }
+    let (target, end) = match *bytes.get(pos)? {
+        b'$' => (Target::Result, pos + 1),
+        b'-' | b'0'..=b'9' => {
+            let digits = if bytes[pos] == b'-' { pos + 1 } else { pos };
+            let end = scan(bytes, digits, |c| c.is_ascii_digit());
+            if end == digits {
+                return None;
+            }
+            let target = match code[pos..end].parse() {
+                Ok(n) => Target::Index(n),
+                Err(_) => Target::OutOfRange(code[pos..end].to_string()),
+            };
+            (target, end)
+        }
+        b'[' => {
+            let name = pos + 1;
+            let end = scan(bytes, name, |c| is_name(c) || c == b'.' || c == b'-');
+            if end == name || bytes.get(end) != Some(&b']') {
+                return None;
+            }
+            (Target::BracketedName(code[name..end].to_string()), end + 1)
+        }
+        c if is_name_start(c) => {
+            let end = scan(bytes, pos, is_name);
+            (Target::Name(code[pos..end].to_string()), end)
+        }
+        _ => return None,
+    };
+    Some((
+        Reference {
+            kind,
+            type_tag,
+            target,
+        },
+        end,
+    ))
+}
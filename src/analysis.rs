@@ -0,0 +1,234 @@
+// Nullable, FIRST and FOLLOW sets of the symbols of a grammar. Mid-rule
+// actions derive only the empty string, so they are left out of the
+// productions without changing any of the sets.
+use crate::grammar::Grammar;
+use crate::symbols::SymbolId;
+use crate::symbols::SymbolTable;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+// An alternative with its symbols resolved, lhs -> rhs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Production {
+    pub lhs: SymbolId,
+    pub rhs: Vec<SymbolId>,
+    // Indices into Grammar::rules and Rule::alternatives
+    pub rule: usize,
+    pub alternative: usize,
+}
+
+// The productions of every nonterminal, in the order of the grammar. Rules
+// given for tokens are left out.
+pub fn productions(grammar: &Grammar, symbols: &SymbolTable) -> Vec<Production> {
+    let mut productions = Vec::new();
+    for (rule_index, rule) in grammar.rules.iter().enumerate() {
+        let Some(lhs) = symbols.get(&rule.name.data) else {
+            continue;
+        };
+        if !symbols.symbol(lhs).rules.contains(&rule_index) {
+            continue;
+        }
+        for (i, alternative) in rule.alternatives.iter().enumerate() {
+            productions.push(Production {
+                lhs,
+                rhs: alternative
+                    .elements
+                    .iter()
+                    .filter_map(|e| symbols.element(e))
+                    .collect(),
+                rule: rule_index,
+                alternative: i,
+            });
+        }
+    }
+    productions
+}
+
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    productions: Vec<Production>,
+    terminals: HashSet<SymbolId>,
+    nullable: HashSet<SymbolId>,
+    // FIRST of every symbol, a terminal being its own FIRST
+    first: HashMap<SymbolId, BTreeSet<SymbolId>>,
+    // FOLLOW of every symbol, with $end following the start symbol
+    follow: HashMap<SymbolId, BTreeSet<SymbolId>>,
+}
+
+impl Analysis {
+    pub fn new(grammar: &Grammar, symbols: &SymbolTable) -> Self {
+        let mut analysis = Analysis {
+            productions: productions(grammar, symbols),
+            terminals: symbols.terminals().map(|(id, _)| id).collect(),
+            nullable: HashSet::new(),
+            first: symbols
+                .symbols()
+                .map(|(id, _)| (id, BTreeSet::new()))
+                .collect(),
+            follow: symbols
+                .symbols()
+                .map(|(id, _)| (id, BTreeSet::new()))
+                .collect(),
+        };
+        for (id, _) in symbols.terminals() {
+            analysis.first.get_mut(&id).unwrap().insert(id);
+        }
+        analysis.compute_nullable();
+        analysis.compute_first();
+        if let Some(start) = symbols.start() {
+            analysis
+                .follow
+                .get_mut(&start)
+                .unwrap()
+                .insert(SymbolTable::END);
+        }
+        analysis.compute_follow();
+        analysis
+    }
+
+    fn compute_nullable(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in &self.productions {
+                if !self.nullable.contains(&production.lhs)
+                    && production.rhs.iter().all(|s| self.nullable.contains(s))
+                {
+                    self.nullable.insert(production.lhs);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    fn compute_first(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in &self.productions {
+                let (first, _) = self.first_of(&production.rhs);
+                let set = self.first.get_mut(&production.lhs).unwrap();
+                let len = set.len();
+                set.extend(first);
+                changed |= set.len() != len;
+            }
+        }
+    }
+
+    fn compute_follow(&mut self) {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in &self.productions {
+                for (i, &symbol) in production.rhs.iter().enumerate() {
+                    let (mut follow, nullable) = self.first_of(&production.rhs[i + 1..]);
+                    if nullable {
+                        follow.extend(self.follow[&production.lhs].iter().copied());
+                    }
+                    let set = self.follow.get_mut(&symbol).unwrap();
+                    let len = set.len();
+                    set.extend(follow);
+                    changed |= set.len() != len;
+                }
+            }
+        }
+    }
+
+    pub fn productions(&self) -> &[Production] {
+        &self.productions
+    }
+
+    // Whether the symbol derives the empty string
+    pub fn is_nullable(&self, id: SymbolId) -> bool {
+        self.nullable.contains(&id)
+    }
+
+    pub fn nullable(&self) -> &HashSet<SymbolId> {
+        &self.nullable
+    }
+
+    // The tokens which can start a string the symbol derives
+    pub fn first(&self, id: SymbolId) -> &BTreeSet<SymbolId> {
+        &self.first[&id]
+    }
+
+    pub fn first_sets(&self) -> &HashMap<SymbolId, BTreeSet<SymbolId>> {
+        &self.first
+    }
+
+    // The tokens, or $end, which can come right after the symbol
+    pub fn follow(&self, id: SymbolId) -> &BTreeSet<SymbolId> {
+        &self.follow[&id]
+    }
+
+    pub fn follow_sets(&self) -> &HashMap<SymbolId, BTreeSet<SymbolId>> {
+        &self.follow
+    }
+
+    // FIRST of a sequence of symbols, and whether all of it is nullable
+    pub fn first_of(&self, sequence: &[SymbolId]) -> (BTreeSet<SymbolId>, bool) {
+        let mut first = BTreeSet::new();
+        for symbol in sequence {
+            first.extend(self.first[symbol].iter().copied());
+            if !self.nullable.contains(symbol) {
+                return (first, false);
+            }
+        }
+        (first, true)
+    }
+
+    // The strings of up to k tokens which can start a string each symbol
+    // derives. Strings shorter than k are whole derivations, so the empty
+    // string is in the set of a nullable symbol.
+    pub fn first_k(&self, k: usize) -> HashMap<SymbolId, BTreeSet<Vec<SymbolId>>> {
+        let mut sets = self
+            .first
+            .keys()
+            .map(|&id| {
+                let mut set = BTreeSet::new();
+                if self.terminals.contains(&id) {
+                    set.insert(std::iter::once(id).take(k).collect());
+                }
+                (id, set)
+            })
+            .collect::<HashMap<_, _>>();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in &self.productions {
+                let mut strings = BTreeSet::from([Vec::new()]);
+                for symbol in &production.rhs {
+                    strings = concat_k(&strings, &sets[symbol], k);
+                }
+                let set = sets.get_mut(&production.lhs).unwrap();
+                let len = set.len();
+                set.extend(strings);
+                changed |= set.len() != len;
+            }
+        }
+        sets
+    }
+}
+
+// Every string of `prefixes` followed by every string of `suffixes`, cut to
+// k tokens
+fn concat_k(
+    prefixes: &BTreeSet<Vec<SymbolId>>,
+    suffixes: &BTreeSet<Vec<SymbolId>>,
+    k: usize,
+) -> BTreeSet<Vec<SymbolId>> {
+    let mut strings = BTreeSet::new();
+    for prefix in prefixes {
+        if prefix.len() >= k {
+            strings.insert(prefix.clone());
+            continue;
+        }
+        for suffix in suffixes {
+            let mut string = prefix.clone();
+            string.extend(suffix.iter().take(k - prefix.len()));
+            strings.insert(string);
+        }
+    }
+    strings
+}
@@ -0,0 +1,997 @@
+// A C parser in the format of Bison's yacc.c skeleton: the parse tables
+// packed into yytable and yycheck the way Bison packs them, and a yyparse
+// function driving them, with yacc's error recovery through the error
+// token. The header Bison writes as y.tab.h declares the token codes and
+// value types for lexers compiled separately.
+//
+// Bison numbers the symbols with $undefined after error and $accept before
+// the nonterminals, and the rules from 1 with the $accept rule first, so
+// that 0 can stand for an error in yydefact. The lexer returns characters
+// as themselves and named tokens from 258, in the order they are declared.
+use crate::action::Reference;
+use crate::action::ReferenceKind;
+use crate::action::Target;
+use crate::diagnostics::Diagnostic;
+use crate::grammar::Alternative;
+use crate::grammar::Directive;
+use crate::grammar::Grammar;
+use crate::grammar::Rule;
+use crate::lr::Automaton;
+use crate::symbols::SymbolId;
+use crate::symbols::SymbolKind;
+use crate::symbols::SymbolTable;
+use crate::tables::Action;
+use crate::tables::Tables;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// The code yylex returns for each token of the symbol table: 0 for $end,
+// 256 for error, the character for a character literal, and 258 on for the
+// others
+pub fn token_codes(symbols: &SymbolTable) -> Vec<i32> {
+    let mut next = 258;
+    symbols
+        .terminals()
+        .map(|(id, symbol)| match symbol.kind {
+            _ if id == SymbolTable::END => 0,
+            _ if id == SymbolTable::ERROR => 256,
+            SymbolKind::Char(c) => c as i32,
+            _ => {
+                next += 1;
+                next - 1
+            }
+        })
+        .collect()
+}
+
+// The tables of Bison's skeletons, named like the arrays they become
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BisonTables {
+    // The symbol number of each token code
+    pub yytranslate: Vec<i32>,
+    // The name of each symbol number
+    pub yytname: Vec<String>,
+    // Where the actions of each state start in yytable, or pact_ninf when
+    // the state only has its default reduction
+    pub yypact: Vec<i32>,
+    // The rule to reduce by in each state when yytable has no action, 0 for
+    // an error
+    pub yydefact: Vec<i32>,
+    // Where the gotos on each nonterminal start in yytable
+    pub yypgoto: Vec<i32>,
+    // The state to go to on each nonterminal when yytable has none
+    pub yydefgoto: Vec<i32>,
+    // Shifts as positive states, reductions as negative rules, and table_ninf
+    // for errors forced by %nonassoc
+    pub yytable: Vec<i32>,
+    // The token or state each entry of yytable is for, -1 when unused
+    pub yycheck: Vec<i32>,
+    // The left-hand side and length of each rule
+    pub yyr1: Vec<i32>,
+    pub yyr2: Vec<i32>,
+    // The state reached by shifting $end, in which the input is accepted
+    pub final_state: usize,
+    pub ntokens: usize,
+    pub pact_ninf: i32,
+    pub table_ninf: i32,
+}
+
+impl BisonTables {
+    pub fn new(symbols: &SymbolTable, automaton: &Automaton, tables: &Tables) -> Self {
+        let tokens = symbols.terminals().count();
+        let ntokens = tokens + 1;
+        let number = |id: SymbolId| match id {
+            _ if id == automaton.accept() => ntokens as i32,
+            _ if id.0 <= SymbolTable::ERROR.0 => id.0 as i32,
+            _ if id.0 < tokens => id.0 as i32 + 1,
+            _ => id.0 as i32 + 2,
+        };
+
+        let codes = token_codes(symbols);
+        let mut yytranslate = vec![2; *codes.iter().max().unwrap_or(&0).max(&257) as usize + 1];
+        yytranslate[257] = 2;
+        for (i, &code) in codes.iter().enumerate() {
+            yytranslate[code as usize] = number(SymbolId(i));
+        }
+
+        let mut yytname = Vec::new();
+        for (id, symbol) in symbols.terminals() {
+            yytname.push(symbol.alias.clone().unwrap_or(symbol.name.clone()));
+            if id == SymbolTable::ERROR {
+                yytname.push("$undefined".to_string());
+            }
+        }
+        yytname.push("$accept".to_string());
+        yytname.extend(symbols.nonterminals().map(|(_, s)| s.name.clone()));
+
+        let mut yyr1 = vec![0];
+        let mut yyr2 = vec![0];
+        for production in &automaton.productions {
+            yyr1.push(number(production.lhs));
+            yyr2.push(production.rhs.len() as i32);
+        }
+
+        // The actions of each state which are not its default, and those of
+        // the gotos on each nonterminal, as vectors to pack into yytable.
+        // Errors are marked with i32::MIN until table_ninf is known.
+        let final_state = automaton.states.len();
+        let mut yydefact = Vec::new();
+        let mut vectors = Vec::new();
+        for row in &tables.action {
+            let mut counts = BTreeMap::<usize, usize>::new();
+            for action in row.values() {
+                if let Action::Reduce(production) = action {
+                    *counts.entry(*production).or_default() += 1;
+                }
+            }
+            // Like Bison, no default reduction where error is shifted
+            let default = match row.get(&SymbolTable::ERROR) {
+                Some(Action::Shift(_)) => None,
+                _ => counts
+                    .iter()
+                    .max_by_key(|&(production, count)| (count, std::cmp::Reverse(production)))
+                    .map(|(&production, _)| production),
+            };
+            yydefact.push(default.map_or(0, |production| production as i32 + 1));
+            let mut vector = Vec::new();
+            for (&token, action) in row {
+                let value = match *action {
+                    Action::Shift(state) => state as i32,
+                    Action::Accept => final_state as i32,
+                    Action::Reduce(production) if Some(production) == default => continue,
+                    Action::Reduce(production) => -(production as i32 + 1),
+                    Action::Error if default.is_none() => continue,
+                    Action::Error => i32::MIN,
+                };
+                vector.push((number(token), value));
+            }
+            vectors.push(vector);
+        }
+        // The state in which $end has been shifted has no actions
+        yydefact.push(0);
+        vectors.push(Vec::new());
+
+        let mut yydefgoto = vec![0];
+        vectors.push(Vec::new());
+        for (id, _) in symbols.nonterminals() {
+            let mut counts = BTreeMap::<usize, usize>::new();
+            for row in &tables.goto {
+                if let Some(&state) = row.get(&id) {
+                    *counts.entry(state).or_default() += 1;
+                }
+            }
+            let default = counts
+                .iter()
+                .max_by_key(|&(state, count)| (count, std::cmp::Reverse(state)))
+                .map_or(0, |(&state, _)| state);
+            yydefgoto.push(default as i32);
+            let vector = tables
+                .goto
+                .iter()
+                .enumerate()
+                .filter_map(|(from, row)| match row.get(&id) {
+                    Some(&to) if to != default => Some((from as i32, to as i32)),
+                    _ => None,
+                })
+                .collect();
+            vectors.push(vector);
+        }
+
+        let (bases, mut yytable, yycheck) = pack(&vectors);
+        let pact_ninf = bases.iter().flatten().copied().min().unwrap_or(0).min(0) - 1;
+        let bases = bases
+            .into_iter()
+            .map(|base| base.unwrap_or(pact_ninf))
+            .collect::<Vec<_>>();
+        let table_ninf = yytable
+            .iter()
+            .copied()
+            .filter(|&value| value != i32::MIN)
+            .min()
+            .unwrap_or(0)
+            .min(0)
+            - 1;
+        for value in &mut yytable {
+            if *value == i32::MIN {
+                *value = table_ninf;
+            }
+        }
+        let nstates = final_state + 1;
+        BisonTables {
+            yytranslate,
+            yytname,
+            yypact: bases[..nstates].to_vec(),
+            yydefact,
+            yypgoto: bases[nstates..].to_vec(),
+            yydefgoto,
+            yytable,
+            yycheck,
+            yyr1,
+            yyr2,
+            final_state,
+            ntokens,
+            pact_ninf,
+            table_ninf,
+        }
+    }
+
+    // The index of the last entry of yytable
+    pub fn last(&self) -> usize {
+        self.yytable.len() - 1
+    }
+}
+
+// Packs the vectors of (key, value) into one table, with a base for each so
+// that the value of a key is at base + key, where the check table holds the
+// key. Like Bison, the widest vectors go first, each at the lowest base at
+// which its entries fit, and no two different vectors share a base, so a
+// key is never found in the entries of another vector.
+#[allow(clippy::type_complexity)]
+fn pack(vectors: &[Vec<(i32, i32)>]) -> (Vec<Option<i32>>, Vec<i32>, Vec<i32>) {
+    let mut order = (0..vectors.len())
+        .filter(|&i| !vectors[i].is_empty())
+        .collect::<Vec<_>>();
+    let width = |vector: &[(i32, i32)]| vector[vector.len() - 1].0 - vector[0].0;
+    order.sort_by_key(|&i| {
+        (
+            std::cmp::Reverse(width(&vectors[i])),
+            std::cmp::Reverse(vectors[i].len()),
+            i,
+        )
+    });
+
+    let mut bases = vec![None; vectors.len()];
+    let mut table = Vec::new();
+    let mut check = Vec::<i32>::new();
+    let mut placed = HashMap::<&[(i32, i32)], i32>::new();
+    let mut used = std::collections::HashSet::new();
+    let mut lowest = 0;
+    for i in order {
+        let vector = &vectors[i][..];
+        if let Some(&base) = placed.get(vector) {
+            bases[i] = Some(base);
+            continue;
+        }
+        let mut base = lowest - vector[0].0;
+        loop {
+            let fits = vector.iter().all(|&(key, _)| {
+                let at = (base + key) as usize;
+                check.get(at).is_none_or(|&c| c == -1)
+            });
+            if fits && !used.contains(&base) {
+                break;
+            }
+            base += 1;
+        }
+        for &(key, value) in vector {
+            let at = (base + key) as usize;
+            if at >= table.len() {
+                table.resize(at + 1, 0);
+                check.resize(at + 1, -1);
+            }
+            table[at] = value;
+            check[at] = key;
+        }
+        while check.get(lowest as usize).is_some_and(|&c| c != -1) {
+            lowest += 1;
+        }
+        used.insert(base);
+        placed.insert(vector, base);
+        bases[i] = Some(base);
+    }
+    if table.is_empty() {
+        table.push(0);
+        check.push(-1);
+    }
+    (bases, table, check)
+}
+
+// Generates the C parser, or reports what prevents it. The grammar is
+// expected to have passed check_symbols and check_actions.
+pub fn generate(grammar: &Grammar) -> Result<String, Vec<Diagnostic>> {
+    let grammar = grammar.desugar_mid_rule_actions();
+    let (symbols, _) = SymbolTable::new(&grammar);
+    if symbols.start().is_none() {
+        return Err(vec![Diagnostic::error(
+            "the grammar has no rules".to_string(),
+            grammar.epilogue.span.clone(),
+        )]);
+    }
+    let automaton = Automaton::lalr(&grammar, &symbols);
+    let tables = Tables::new(&automaton, &symbols);
+    let bison = BisonTables::new(&symbols, &automaton, &tables);
+    let options = Options::new(&grammar);
+
+    let mut diagnostics = Vec::new();
+    let contexts = symbols.mid_rule_contexts(&grammar);
+    let mut actions = String::new();
+    for (p, production) in automaton.productions.iter().enumerate().skip(1) {
+        let rule = &grammar.rules[production.rule];
+        let alternative = &rule.alternatives[production.alternative];
+        let Some(action) = &alternative.action else {
+            continue;
+        };
+        let translate = |reference: &Reference| {
+            translate(
+                &grammar,
+                &symbols,
+                &contexts,
+                &options,
+                (production.lhs, &production.rhs),
+                (rule, alternative),
+                reference,
+            )
+        };
+        for reference in action.references() {
+            if let Err(message) = translate(&reference.data) {
+                diagnostics.push(Diagnostic::error(message, reference.span.clone()));
+            }
+        }
+        let code = action.rewrite(|reference| translate(reference).ok());
+        writeln!(
+            actions,
+            "    case {}:\n      {}\n      break;\n",
+            p + 1,
+            code
+        )
+        .unwrap();
+    }
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    let mut out = String::new();
+    out.push_str("/* A Bison-compatible LALR(1) parser generated by yacc-parser.  */\n\n");
+    writeln!(
+        out,
+        "#define YYBISON 1\n#define YYPURE {}\n",
+        options.pure as i32
+    )
+    .unwrap();
+    if let Some(prefix) = &options.prefix {
+        let mut renamed = vec!["parse", "lex", "error", "debug", "nerrs"];
+        if !options.pure {
+            renamed.extend(["lval", "char"]);
+            if options.locations {
+                renamed.push("lloc");
+            }
+        }
+        for name in renamed {
+            writeln!(out, "#define yy{} {}{}", name, prefix, name).unwrap();
+        }
+        out.push('\n');
+    }
+    // Like Bison, the prologues after %union come after the types, so
+    // that they can use them
+    let (before, after) = grammar.prologues.iter().partition::<Vec<_>, _>(|prologue| {
+        options
+            .union_start
+            .is_none_or(|start| prologue.span.start < start)
+    });
+    for prologue in before {
+        writeln!(out, "{}", prologue.data).unwrap();
+    }
+    out.push_str("#include <stddef.h>\n#include <stdlib.h>\n\n");
+    out.push_str(&definitions(&symbols, &options));
+    for prologue in after {
+        writeln!(out, "{}", prologue.data).unwrap();
+    }
+
+    write_tables(&mut out, &bison);
+    out.push_str(&skeleton(&options).replace("@ACTIONS@", &actions));
+    out.push_str(&grammar.epilogue.data);
+    Ok(out)
+}
+
+// The header declaring what the lexer and the users of the parser need: the
+// codes of the named tokens, the types of the values and locations, and
+// yyparse, named with the %name-prefix
+pub fn header(grammar: &Grammar) -> String {
+    let (symbols, _) = SymbolTable::new(grammar);
+    let options = Options::new(grammar);
+    let prefix = options.prefix.as_deref().unwrap_or("yy");
+    let guard = format!("YY_{}_Y_TAB_H_INCLUDED", prefix.to_ascii_uppercase());
+    let mut out = format!(
+        "/* Token definitions of a parser generated by yacc-parser.  */\n\n\
+         #ifndef {guard}\n\
+         # define {guard}\n\n",
+        guard = guard
+    );
+    out.push_str(&definitions(&symbols, &options));
+    let tokens = named_tokens(&symbols);
+    if !tokens.is_empty() {
+        out.push_str("/* Token codes, for yacc compatibility.  */\n");
+        for (name, code) in tokens {
+            writeln!(out, "#define {} {}", name, code).unwrap();
+        }
+        out.push('\n');
+    }
+    if !options.pure {
+        writeln!(out, "extern YYSTYPE {}lval;", prefix).unwrap();
+        if options.locations {
+            writeln!(out, "extern YYLTYPE {}lloc;", prefix).unwrap();
+        }
+        out.push('\n');
+    }
+    writeln!(
+        out,
+        "int {}parse ({});\n\n#endif",
+        prefix,
+        parameters(&[], &options.parse_params)
+    )
+    .unwrap();
+    out
+}
+
+// The named tokens and their codes, leaving out those only written as
+// strings
+fn named_tokens(symbols: &SymbolTable) -> Vec<(&str, i32)> {
+    let codes = token_codes(symbols);
+    symbols
+        .terminals()
+        .filter(|(id, symbol)| {
+            symbol.kind == SymbolKind::Terminal
+                && id.0 > SymbolTable::ERROR.0
+                && symbol.is_identifier()
+        })
+        .map(|(id, symbol)| (symbol.name.as_str(), codes[id.0]))
+        .collect()
+}
+
+// The enum of the token codes, YYSTYPE, and YYLTYPE with locations, which
+// both the parser and its header define
+fn definitions(symbols: &SymbolTable, options: &Options) -> String {
+    let mut out = String::new();
+    let tokens = named_tokens(symbols);
+    if !tokens.is_empty() {
+        out.push_str("#ifndef YYTOKENTYPE\n# define YYTOKENTYPE\nenum yytokentype\n  {\n");
+        for (name, code) in tokens {
+            writeln!(out, "    {} = {},", name, code).unwrap();
+        }
+        out.push_str("  };\n#endif\n\n");
+    }
+    out.push_str("#if ! defined YYSTYPE && ! defined YYSTYPE_IS_DECLARED\n");
+    match &options.union {
+        Some(code) => writeln!(out, "typedef union YYSTYPE\n{{{}}} YYSTYPE;", code),
+        None => writeln!(out, "typedef int YYSTYPE;"),
+    }
+    .unwrap();
+    out.push_str("# define YYSTYPE_IS_DECLARED 1\n#endif\n\n");
+    if options.locations {
+        out.push_str(
+            "#if ! defined YYLTYPE && ! defined YYLTYPE_IS_DECLARED\n\
+             typedef struct YYLTYPE\n\
+             {\n  \
+             int first_line;\n  \
+             int first_column;\n  \
+             int last_line;\n  \
+             int last_column;\n\
+             } YYLTYPE;\n\
+             # define YYLTYPE_IS_DECLARED 1\n\
+             #endif\n\n",
+        );
+    }
+    out
+}
+
+// What the directives ask of the parser
+struct Options {
+    prefix: Option<String>,
+    pure: bool,
+    locations: bool,
+    // The parameters of yyparse and of yylex, as declared
+    parse_params: Vec<String>,
+    lex_params: Vec<String>,
+    union: Option<String>,
+    // Where the %union is in the source
+    union_start: Option<usize>,
+}
+
+impl Options {
+    fn new(grammar: &Grammar) -> Self {
+        let mut options = Options {
+            prefix: None,
+            pure: false,
+            locations: false,
+            parse_params: Vec::new(),
+            lex_params: Vec::new(),
+            union: None,
+            union_start: None,
+        };
+        for directive in &grammar.directives {
+            match &directive.data {
+                Directive::NamePrefix { prefix } => options.prefix = Some(prefix.clone()),
+                Directive::PureParser => options.pure = true,
+                Directive::Locations => options.locations = true,
+                Directive::ParseParam { params } => {
+                    options.parse_params.push(params.trim().to_string())
+                }
+                Directive::LexProgram { params } => {
+                    options.lex_params.push(params.trim().to_string())
+                }
+                Directive::Union { code } => {
+                    options.union = Some(code.clone());
+                    options.union_start = Some(directive.span.start);
+                }
+                _ => {}
+            }
+        }
+        // Like Bison, using a location asks for them
+        options.locations |= grammar.rules.iter().any(|rule| {
+            rule.alternatives.iter().any(|alternative| {
+                alternative
+                    .action
+                    .iter()
+                    .flat_map(|action| action.references())
+                    .any(|reference| reference.data.kind == ReferenceKind::Location)
+            })
+        });
+        options
+    }
+}
+
+// The C for a reference in the action of a production, with the value
+// stack holding the values of its symbols on top
+fn translate(
+    grammar: &Grammar,
+    symbols: &SymbolTable,
+    contexts: &HashMap<SymbolId, (usize, Vec<SymbolId>)>,
+    options: &Options,
+    (lhs, rhs): (SymbolId, &[SymbolId]),
+    (rule, alternative): (&Rule, &Alternative),
+    reference: &Reference,
+) -> Result<String, String> {
+    let len = rhs.len() as i32;
+    let n = match &reference.target {
+        Target::Result => None,
+        Target::Index(n) => Some(*n),
+        Target::OutOfRange(_) => return Err(format!("integer out of range: {}", reference)),
+        Target::Name(name) | Target::BracketedName(name) => {
+            match rule.named_references(alternative, rhs.len(), name)[..] {
+                [(n, _)] => n,
+                _ => return Err(format!("invalid reference: {}", reference)),
+            }
+        }
+    };
+    if n.is_some_and(|n| n > len) {
+        return Err(format!("invalid reference: {}", reference));
+    }
+    if reference.kind == ReferenceKind::Location {
+        return Ok(match n {
+            None => "(yyloc)".to_string(),
+            Some(n) => format!("(yylsp[({}) - ({})])", n, len),
+        });
+    }
+    let place = match n {
+        None => "(yyval)".to_string(),
+        Some(n) => format!("(yyvsp[({}) - ({})])", n, len),
+    };
+    if options.union.is_none() {
+        return Ok(place);
+    }
+    // The symbol whose type the value has
+    let symbol = match n {
+        None => Some(lhs),
+        Some(n) if n >= 1 => Some(rhs[n as usize - 1]),
+        Some(n) => contexts.get(&lhs).and_then(|(i, before)| {
+            let i = *i as i32 + n - 1;
+            (i >= 0).then(|| before[i as usize])
+        }),
+    };
+    let type_name = reference.type_tag.as_deref().or_else(|| {
+        let symbol = symbols.symbol(symbol?);
+        match symbol.name.starts_with("$@") {
+            // The type given with $<type>$ in the mid-rule action
+            true => grammar.rules[symbol.rules[0]].alternatives[0]
+                .action
+                .iter()
+                .flat_map(|action| action.references())
+                .find_map(|r| match (&r.data.target, &r.data.type_tag) {
+                    (Target::Result, Some(tag)) => Some(tag.as_str()),
+                    _ => None,
+                }),
+            false => symbol.type_name.as_deref(),
+        }
+    });
+    match type_name {
+        Some(type_name) => Ok(format!(
+            "{}.{}",
+            place,
+            type_name.trim_start_matches('<').trim_end_matches('>')
+        )),
+        None => {
+            let name = symbol.map_or("?", |s| symbols.symbol(s).name.as_str());
+            Err(format!("{} of '{}' has no declared type", reference, name))
+        }
+    }
+}
+
+fn write_tables(out: &mut String, tables: &BisonTables) {
+    writeln!(
+        out,
+        "#define YYFINAL  {}\n\
+         #define YYLAST   {}\n\
+         #define YYNTOKENS  {}\n\
+         #define YYNNTS  {}\n\
+         #define YYNRULES  {}\n\
+         #define YYNSTATES  {}\n\
+         #define YYMAXUTOK   {}\n\n\
+         #define YYTRANSLATE(YYX) \\\n  \
+         ((YYX) >= 0 && (YYX) <= YYMAXUTOK ? yytranslate[YYX] : 2)\n\n\
+         #define YYPACT_NINF ({})\n\
+         #define yypact_value_is_default(Yyn) ((Yyn) == YYPACT_NINF)\n\
+         #define YYTABLE_NINF ({})\n\
+         #define yytable_value_is_error(Yyn) ((Yyn) == YYTABLE_NINF)\n",
+        tables.final_state,
+        tables.last(),
+        tables.ntokens,
+        tables.yydefgoto.len(),
+        tables.yyr1.len() - 1,
+        tables.yypact.len(),
+        tables.yytranslate.len() - 1,
+        tables.pact_ninf,
+        tables.table_ninf
+    )
+    .unwrap();
+    for (name, values) in [
+        ("yytranslate", &tables.yytranslate),
+        ("yypact", &tables.yypact),
+        ("yydefact", &tables.yydefact),
+        ("yypgoto", &tables.yypgoto),
+        ("yydefgoto", &tables.yydefgoto),
+        ("yytable", &tables.yytable),
+        ("yycheck", &tables.yycheck),
+        ("yyr1", &tables.yyr1),
+        ("yyr2", &tables.yyr2),
+    ] {
+        write_array(out, name, values);
+    }
+    // Like Bison, only kept for those who ask for the names
+    out.push_str("\n#if YYDEBUG || YYERROR_VERBOSE || YYTOKEN_TABLE\nstatic const char *const yytname[] =\n{\n ");
+    for name in &tables.yytname {
+        write!(
+            out,
+            " \"{}\",",
+            name.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+        .unwrap();
+    }
+    out.push_str(" 0\n};\n#endif\n");
+}
+
+// An array of the smallest type Bison would use for the values, ten to a
+// line
+fn write_array(out: &mut String, name: &str, values: &[i32]) {
+    let min = values.iter().copied().min().unwrap_or(0);
+    let max = values.iter().copied().max().unwrap_or(0);
+    let c_type = if min >= 0 && max <= u8::MAX as i32 {
+        "unsigned char"
+    } else if min >= i8::MIN as i32 && max <= i8::MAX as i32 {
+        "signed char"
+    } else if min >= i16::MIN as i32 && max <= i16::MAX as i32 {
+        "short"
+    } else {
+        "int"
+    };
+    writeln!(out, "\nstatic const {} {}[] =\n{{", c_type, name).unwrap();
+    for (i, chunk) in values.chunks(10).enumerate() {
+        out.push(' ');
+        for (j, value) in chunk.iter().enumerate() {
+            let last = i * 10 + j + 1 == values.len();
+            write!(out, " {:>5}{}", value, if last { "" } else { "," }).unwrap();
+        }
+        out.push('\n');
+    }
+    out.push_str("};\n");
+}
+
+// The parameters of a function: the declared ones after those given first,
+// or void
+fn parameters(first: &[&str], declared: &[String]) -> String {
+    let all = first
+        .iter()
+        .map(|p| p.to_string())
+        .chain(declared.iter().cloned())
+        .collect::<Vec<_>>();
+    if all.is_empty() {
+        "void".to_string()
+    } else {
+        all.join(", ")
+    }
+}
+
+// The arguments passing declared parameters on, the names at their ends
+fn arguments(first: &[&str], declared: &[String]) -> String {
+    first
+        .iter()
+        .map(|a| a.to_string())
+        .chain(declared.iter().map(|param| {
+            let name_start = param
+                .rfind(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .map_or(0, |i| i + 1);
+            param[name_start..].to_string()
+        }))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// yyparse, after the tables. The actions go in place of @ACTIONS@.
+fn skeleton(options: &Options) -> String {
+    let locations = options.locations;
+    let pure_lex = match (options.pure, locations) {
+        (false, _) => vec![],
+        (true, false) => vec!["&yylval"],
+        (true, true) => vec!["&yylval", "&yylloc"],
+    };
+    let error_first = if options.pure && locations {
+        vec!["&yylloc"]
+    } else {
+        vec![]
+    };
+    let mut error_args = arguments(&error_first, &options.parse_params);
+    if !error_args.is_empty() {
+        error_args.push_str(", ");
+    }
+    let lex = format!("yylex ({})", arguments(&pure_lex, &options.lex_params));
+    let state = format!(
+        "int yychar;\n\
+         YYSTYPE yylval;\n\
+         {}int yynerrs;\n",
+        if locations {
+            "YYLTYPE yylloc = { 1, 1, 1, 1 };\n"
+        } else {
+            ""
+        }
+    );
+    let (global, local) = if options.pure {
+        (String::new(), state.replace('\n', "\n  "))
+    } else {
+        (format!("{}\n", state), String::new())
+    };
+    // The lines handling the location stack, only with locations
+    let l = |line: &str| {
+        if locations {
+            line.to_string()
+        } else {
+            String::new()
+        }
+    };
+
+    format!(
+        r#"
+#define YYINITDEPTH 200
+#define YYMAXDEPTH 10000
+#define YYEMPTY (-2)
+#define YYEOF 0
+#define YYTERROR 1
+#define yyerrok (yyerrstatus = 0)
+#define yyclearin (yychar = YYEMPTY)
+#define YYACCEPT goto yyacceptlab
+#define YYABORT goto yyabortlab
+#define YYERROR goto yyerrorlab
+{lloc_default}
+{global}int
+yyparse ({params})
+{{
+  {local}int yystate = 0;
+  int yyerrstatus = 0;
+  int yyn;
+  int yyresult;
+  int yytoken = 0;
+  int yylen = 0;
+  YYSTYPE yyval;
+{yyloc}  ptrdiff_t yystacksize = YYINITDEPTH;
+  int *yyss = (int *) malloc (YYINITDEPTH * sizeof (int));
+  YYSTYPE *yyvs = (YYSTYPE *) malloc (YYINITDEPTH * sizeof (YYSTYPE));
+{yyls}  int *yyssp = yyss;
+  YYSTYPE *yyvsp = yyvs;
+{yylsp}
+  if (!yyss || !yyvs{yyls_check})
+    goto yyexhaustedlab;
+  yynerrs = 0;
+  yychar = YYEMPTY;
+{yylsp_init}  goto yysetstate;
+
+yynewstate:
+  yyssp++;
+
+yysetstate:
+  *yyssp = yystate;
+  if (yyss + yystacksize - 1 <= yyssp)
+    {{
+      ptrdiff_t yysize = yyssp - yyss + 1;
+      if (YYMAXDEPTH <= yystacksize)
+        goto yyexhaustedlab;
+      yystacksize *= 2;
+      if (YYMAXDEPTH < yystacksize)
+        yystacksize = YYMAXDEPTH;
+      {{
+        int *yyss1 = (int *) realloc (yyss, yystacksize * sizeof (int));
+        YYSTYPE *yyvs1 = (YYSTYPE *) realloc (yyvs, yystacksize * sizeof (YYSTYPE));
+        if (yyss1)
+          yyss = yyss1;
+        if (yyvs1)
+          yyvs = yyvs1;
+{grow_yyls}        if (!yyss1 || !yyvs1{yyls1_check})
+          goto yyexhaustedlab;
+      }}
+      yyssp = yyss + yysize - 1;
+      yyvsp = yyvs + yysize - 1;
+{yylsp_grow}    }}
+  if (yystate == YYFINAL)
+    YYACCEPT;
+
+  yyn = yypact[yystate];
+  if (yypact_value_is_default (yyn))
+    goto yydefault;
+  if (yychar == YYEMPTY)
+    yychar = {lex};
+  if (yychar <= YYEOF)
+    {{
+      yychar = YYEOF;
+      yytoken = 0;
+    }}
+  else
+    yytoken = YYTRANSLATE (yychar);
+  yyn += yytoken;
+  if (yyn < 0 || YYLAST < yyn || yycheck[yyn] != yytoken)
+    goto yydefault;
+  yyn = yytable[yyn];
+  if (yyn <= 0)
+    {{
+      if (yytable_value_is_error (yyn))
+        goto yyerrlab;
+      yyn = -yyn;
+      goto yyreduce;
+    }}
+  if (yyerrstatus)
+    yyerrstatus--;
+  yystate = yyn;
+  *++yyvsp = yylval;
+{push_yylloc}  yychar = YYEMPTY;
+  goto yynewstate;
+
+yydefault:
+  yyn = yydefact[yystate];
+  if (yyn == 0)
+    goto yyerrlab;
+
+yyreduce:
+  yylen = yyr2[yyn];
+  yyval = yyvsp[1 - yylen];
+{yyloc_default}  switch (yyn)
+    {{
+@ACTIONS@    default: break;
+    }}
+  yyvsp -= yylen;
+  yyssp -= yylen;
+{pop_yylsp}  yylen = 0;
+  *++yyvsp = yyval;
+{push_yyloc}  {{
+    const int yylhs = yyr1[yyn] - YYNTOKENS;
+    const int yyi = yypgoto[yylhs] + *yyssp;
+    yystate = (0 <= yyi && yyi <= YYLAST && yycheck[yyi] == *yyssp
+               ? yytable[yyi]
+               : yydefgoto[yylhs]);
+  }}
+  goto yynewstate;
+
+yyerrlab:
+  if (!yyerrstatus)
+    {{
+      ++yynerrs;
+      yyerror ({error_args}"syntax error");
+    }}
+  if (yyerrstatus == 3)
+    {{
+      /* The lookahead could not be shifted after an error: discard it.  */
+      if (yychar <= YYEOF)
+        {{
+          if (yychar == YYEOF)
+            YYABORT;
+        }}
+      else
+        yychar = YYEMPTY;
+    }}
+  goto yyerrlab1;
+
+yyerrorlab:
+  /* Keeps the label used when no action uses YYERROR.  */
+  if (0)
+    YYERROR;
+  yyvsp -= yylen;
+  yyssp -= yylen;
+{pop_yylsp}  yylen = 0;
+  yystate = *yyssp;
+
+yyerrlab1:
+  yyerrstatus = 3;
+  for (;;)
+    {{
+      yyn = yypact[yystate];
+      if (!yypact_value_is_default (yyn))
+        {{
+          yyn += YYTERROR;
+          if (0 <= yyn && yyn <= YYLAST && yycheck[yyn] == YYTERROR)
+            {{
+              yyn = yytable[yyn];
+              if (0 < yyn)
+                break;
+            }}
+        }}
+      if (yyssp == yyss)
+        YYABORT;
+      yyvsp--;
+      yyssp--;
+{pop_one_yylsp}      yystate = *yyssp;
+    }}
+  *++yyvsp = yylval;
+{push_yylloc}  yystate = yyn;
+  goto yynewstate;
+
+yyacceptlab:
+  yyresult = 0;
+  goto yyreturn;
+
+yyabortlab:
+  yyresult = 1;
+  goto yyreturn;
+
+yyexhaustedlab:
+  yyerror ({error_args}"memory exhausted");
+  yyresult = 2;
+
+yyreturn:
+  free (yyss);
+  free (yyvs);
+{free_yyls}  return yyresult;
+}}
+"#,
+        lloc_default = l(
+            "
+#ifndef YYLLOC_DEFAULT
+# define YYLLOC_DEFAULT(Current, Rhs, N)                                 \\
+  do                                                                    \\
+    if (N)                                                              \\
+      {                                                                 \\
+        (Current).first_line = (Rhs)[1].first_line;                     \\
+        (Current).first_column = (Rhs)[1].first_column;                 \\
+        (Current).last_line = (Rhs)[N].last_line;                       \\
+        (Current).last_column = (Rhs)[N].last_column;                   \\
+      }                                                                 \\
+    else                                                                \\
+      {                                                                 \\
+        (Current).first_line = (Current).last_line = (Rhs)[0].last_line; \\
+        (Current).first_column = (Current).last_column =               \\
+          (Rhs)[0].last_column;                                         \\
+      }                                                                 \\
+  while (0)
+#endif
+"
+        ),
+        global = global,
+        params = parameters(&[], &options.parse_params),
+        local = local,
+        yyloc = l("  YYLTYPE yyloc;\n"),
+        yyls = l("  YYLTYPE *yyls = (YYLTYPE *) malloc (YYINITDEPTH * sizeof (YYLTYPE));\n"),
+        yylsp = l("  YYLTYPE *yylsp = yyls;\n"),
+        yyls_check = l(" || !yyls"),
+        yylsp_init = l("  yylsp[0] = yylloc;\n"),
+        grow_yyls = l(
+            "        YYLTYPE *yyls1 = (YYLTYPE *) realloc (yyls, yystacksize * sizeof (YYLTYPE));\n        \
+             if (yyls1)\n          \
+             yyls = yyls1;\n"
+        ),
+        yyls1_check = l(" || !yyls1"),
+        yylsp_grow = l("      yylsp = yyls + yysize - 1;\n"),
+        lex = lex,
+        push_yylloc = l("  *++yylsp = yylloc;\n"),
+        yyloc_default = l("  YYLLOC_DEFAULT (yyloc, (yylsp - yylen), yylen);\n"),
+        pop_yylsp = l("  yylsp -= yylen;\n"),
+        push_yyloc = l("  *++yylsp = yyloc;\n"),
+        error_args = error_args,
+        pop_one_yylsp = l("      yylsp--;\n"),
+        free_yyls = l("  free (yyls);\n"),
+    )
+}
@@ -0,0 +1,481 @@
+// Checks of a parsed grammar beyond its syntax, mirroring the diagnostics
+// Bison gives for the same mistakes.
+use crate::action::Action;
+use crate::action::Reference;
+use crate::action::ReferenceKind;
+use crate::action::Target;
+use crate::diagnostics::Diagnostic;
+use crate::grammar::Alternative;
+use crate::grammar::Directive;
+use crate::grammar::Element;
+use crate::grammar::Grammar;
+use crate::grammar::Rule;
+use crate::symbols::SymbolId;
+use crate::symbols::SymbolTable;
+use crate::token::Spanned;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::Range;
+
+// Checks the symbols of a grammar: the problems the symbol table finds, then
+// lints for tokens, nonterminals, alternatives and %type entries which can
+// never take part in a parse. Each lint has a stable code.
+pub fn check_symbols(grammar: &Grammar) -> Vec<Diagnostic> {
+    let (symbols, mut diagnostics) = SymbolTable::new(grammar);
+    diagnostics.extend(unused_tokens(grammar, &symbols));
+    diagnostics.extend(unproductive_nonterminals(grammar, &symbols));
+    diagnostics.extend(unreachable_nonterminals(grammar, &symbols));
+    diagnostics.extend(duplicate_alternatives(grammar, &symbols));
+    diagnostics.extend(dangling_types(grammar, &symbols));
+    diagnostics
+}
+
+// The symbols an alternative refers to, including its %prec token
+fn alternative_symbols<'a>(
+    symbols: &'a SymbolTable,
+    alternative: &'a Alternative,
+) -> impl Iterator<Item = SymbolId> + 'a {
+    let elements = alternative
+        .elements
+        .iter()
+        .filter_map(|e| symbols.element(e));
+    let precedence = alternative
+        .precedence
+        .iter()
+        .filter_map(|name| symbols.get(&name.data));
+    elements.chain(precedence)
+}
+
+// Nonterminals which have rules; the others are undefined and reported by
+// the symbol table already
+fn defined_nonterminals(symbols: &SymbolTable) -> impl Iterator<Item = SymbolId> + '_ {
+    symbols
+        .nonterminals()
+        .filter(|(_, s)| !s.rules.is_empty())
+        .map(|(id, _)| id)
+}
+
+fn alternatives<'a>(
+    grammar: &'a Grammar,
+    symbols: &'a SymbolTable,
+    id: SymbolId,
+) -> impl Iterator<Item = &'a Alternative> {
+    symbols
+        .symbol(id)
+        .rules
+        .iter()
+        .flat_map(|&i| &grammar.rules[i].alternatives)
+}
+
+fn unused_tokens(grammar: &Grammar, symbols: &SymbolTable) -> Vec<Diagnostic> {
+    let used = grammar
+        .rules
+        .iter()
+        .flat_map(|r| &r.alternatives)
+        .flat_map(|a| alternative_symbols(symbols, a))
+        .collect::<HashSet<_>>();
+    symbols
+        .terminals()
+        .filter(|(id, _)| !used.contains(id))
+        .filter_map(|(_, symbol)| {
+            let span = symbol.span.clone()?;
+            Some(
+                Diagnostic::warning(format!("token {} is never used", symbol.name), span)
+                    .with_code("unused-token")
+                    .with_primary_label("declared here".to_string()),
+            )
+        })
+        .collect()
+}
+
+// Nonterminals which derive no string of tokens, because every alternative
+// needs another such nonterminal
+fn unproductive_nonterminals(grammar: &Grammar, symbols: &SymbolTable) -> Vec<Diagnostic> {
+    let mut productive = HashSet::new();
+    loop {
+        let found = defined_nonterminals(symbols)
+            .filter(|id| !productive.contains(id))
+            .filter(|&id| {
+                alternatives(grammar, symbols, id).any(|alternative| {
+                    alternative
+                        .elements
+                        .iter()
+                        .filter_map(|e| symbols.element(e))
+                        .all(|element| {
+                            let symbol = symbols.symbol(element);
+                            symbol.is_terminal()
+                                || symbol.rules.is_empty()
+                                || productive.contains(&element)
+                        })
+                })
+            })
+            .collect::<Vec<_>>();
+        if found.is_empty() {
+            break;
+        }
+        productive.extend(found);
+    }
+    defined_nonterminals(symbols)
+        .filter(|id| !productive.contains(id))
+        .map(|id| {
+            let symbol = symbols.symbol(id);
+            Diagnostic::warning(
+                format!("nonterminal {} derives no string of tokens", symbol.name),
+                symbol.span.clone().unwrap(),
+            )
+            .with_code("unproductive-nonterminal")
+            .with_primary_label("every alternative recurses without end".to_string())
+        })
+        .collect()
+}
+
+fn unreachable_nonterminals(grammar: &Grammar, symbols: &SymbolTable) -> Vec<Diagnostic> {
+    let Some(start) = symbols.start() else {
+        return Vec::new();
+    };
+    let mut reachable = HashSet::from([start]);
+    let mut pending = vec![start];
+    while let Some(id) = pending.pop() {
+        for alternative in alternatives(grammar, symbols, id) {
+            for element in alternative
+                .elements
+                .iter()
+                .filter_map(|e| symbols.element(e))
+            {
+                if reachable.insert(element) {
+                    pending.push(element);
+                }
+            }
+        }
+    }
+    let start = symbols.symbol(start);
+    defined_nonterminals(symbols)
+        .filter(|id| !reachable.contains(id))
+        .map(|id| {
+            let symbol = symbols.symbol(id);
+            Diagnostic::warning(
+                format!("nonterminal {} is unreachable", symbol.name),
+                symbol.span.clone().unwrap(),
+            )
+            .with_code("unreachable-nonterminal")
+            .with_primary_label(format!("not used by {} or its rules", start.name))
+        })
+        .collect()
+}
+
+// Alternatives of a nonterminal with the same symbols, which always make a
+// reduce/reduce conflict whatever their %prec. Mid-rule actions make
+// alternatives differ.
+fn duplicate_alternatives(grammar: &Grammar, symbols: &SymbolTable) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for id in defined_nonterminals(symbols) {
+        let mut seen = HashMap::<_, Range<usize>>::new();
+        for alternative in alternatives(grammar, symbols, id) {
+            let has_actions = alternative
+                .elements
+                .iter()
+                .any(|e| matches!(e, Element::Action(_)));
+            if has_actions {
+                continue;
+            }
+            let key = alternative
+                .elements
+                .iter()
+                .filter_map(|e| symbols.element(e))
+                .collect::<Vec<_>>();
+            match seen.get(&key) {
+                Some(first) => diagnostics.push(
+                    Diagnostic::warning(
+                        format!("duplicate alternative for {}", symbols.symbol(id).name),
+                        alternative.span.clone(),
+                    )
+                    .with_code("duplicate-alternative")
+                    .with_secondary_label(first.clone(), "first given here".to_string()),
+                ),
+                None => {
+                    seen.insert(key, alternative.span.clone());
+                }
+            }
+        }
+    }
+    diagnostics
+}
+
+fn dangling_types(grammar: &Grammar, symbols: &SymbolTable) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for directive in &grammar.directives {
+        let Directive::Type { rule_names, .. } = &directive.data else {
+            continue;
+        };
+        for name in rule_names {
+            if symbols.get(&name.data).is_none() {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        format!("%type given for {}, which is not a symbol", name),
+                        name.span.clone(),
+                    )
+                    .with_code("dangling-type")
+                    .with_primary_label("neither a token nor a nonterminal".to_string()),
+                );
+            }
+        }
+    }
+    diagnostics
+}
+
+// Checks the references of every action against its alternative: $n and @n
+// must name one of its symbols, $$ should be set when the rule has a type,
+// and the values of typed symbols and mid-rule actions should be used.
+pub fn check_actions(grammar: &Grammar) -> Vec<Diagnostic> {
+    let types = declared_types(grammar);
+    let mut diagnostics = Vec::new();
+    for rule in &grammar.rules {
+        for alternative in &rule.alternatives {
+            diagnostics.extend(check_alternative(rule, alternative, &types));
+        }
+    }
+    diagnostics
+}
+
+// The <type> of each symbol declared with %token <type> or %type <type>,
+// keyed by the symbol as written. Token aliases share the token's type.
+fn declared_types(grammar: &Grammar) -> HashMap<&str, &str> {
+    let mut types = HashMap::new();
+    for directive in &grammar.directives {
+        match &directive.data {
+            Directive::Type {
+                type_name,
+                rule_names,
+            } => {
+                for name in rule_names {
+                    types.insert(name.data.as_str(), type_name.data.as_str());
+                }
+            }
+            Directive::Token {
+                token_name: Some(type_name),
+                rule_names,
+            } => {
+                for declaration in rule_names {
+                    types.insert(declaration.name.data.as_str(), type_name.data.as_str());
+                    if let Some(alias) = &declaration.alias {
+                        types.insert(alias.data.as_str(), type_name.data.as_str());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    types
+}
+
+fn check_alternative(
+    rule: &Rule,
+    alternative: &Alternative,
+    types: &HashMap<&str, &str>,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let declared = types.get(rule.name.data.as_str());
+    // Whether the value of $n is used, and whether the mid-rule action at
+    // $n sets its own value, with $0 unused
+    let mut used = vec![false; alternative.elements.len() + 1];
+    let mut set = vec![false; alternative.elements.len() + 1];
+    for (i, element) in alternative.elements.iter().enumerate() {
+        if let Element::Action(action) = element {
+            // $$ in a mid-rule action is its own value, not the rule's
+            set[i + 1] =
+                check_references(rule, alternative, action, i, &mut used, &mut diagnostics);
+        }
+    }
+
+    match &alternative.action {
+        Some(action) => {
+            let visible = alternative.elements.len();
+            let assigned = check_references(
+                rule,
+                alternative,
+                action,
+                visible,
+                &mut used,
+                &mut diagnostics,
+            );
+            if let (Some(type_name), false) = (declared, assigned) {
+                diagnostics.push(
+                    Diagnostic::warning("unset value: $$".to_string(), action.code.span.clone())
+                        .with_code("unset-value")
+                        .with_primary_label("$$ is never assigned".to_string())
+                        .with_secondary_label(
+                            rule.name.span.clone(),
+                            format!("declared with type {}", type_name),
+                        ),
+                );
+            }
+        }
+        None => {
+            // The default action $$ = $1 uses $1, but none of the values
+            // after it, or has nothing to copy
+            match (used.get_mut(1), declared) {
+                (Some(first), _) => *first = true,
+                (None, Some(type_name)) => diagnostics.push(
+                    Diagnostic::warning(
+                        "empty rule for typed nonterminal, and no action".to_string(),
+                        alternative.span.clone(),
+                    )
+                    .with_code("empty-typed-rule")
+                    .with_secondary_label(
+                        rule.name.span.clone(),
+                        format!("declared with type {}", type_name),
+                    ),
+                ),
+                (None, None) => {}
+            }
+        }
+    }
+
+    // Bison warns about the unused values of symbols with a %destructor,
+    // which cannot be declared here, so a declared type stands in for one.
+    // As in Bison, the value of a mid-rule action is checked when it is set
+    // or used: one must not go without the other.
+    for (i, element) in alternative.elements.iter().enumerate() {
+        let n = i + 1;
+        let label = match element {
+            Element::Action(action) => {
+                if used[n] && !set[n] {
+                    diagnostics.push(
+                        Diagnostic::warning(
+                            "unset value: $$".to_string(),
+                            action.code.span.clone(),
+                        )
+                        .with_code("unset-value")
+                        .with_primary_label(format!("$$ is never assigned, but used as ${}", n)),
+                    );
+                }
+                if !set[n] {
+                    continue;
+                }
+                "set by this mid-rule action".to_string()
+            }
+            _ => match types.get(element.text().data.as_str()) {
+                Some(type_name) => format!("has type {}", type_name),
+                None => continue,
+            },
+        };
+        if !used[n] {
+            diagnostics.push(
+                Diagnostic::warning(format!("unused value: ${}", n), element.span())
+                    .with_code("unused-value")
+                    .with_primary_label(label),
+            );
+        }
+    }
+    diagnostics
+}
+
+// Checks the references of an action which sees the first `visible` elements
+// of the alternative, marking the values it uses. Returns whether it uses $$.
+fn check_references(
+    rule: &Rule,
+    alternative: &Alternative,
+    action: &Action,
+    visible: usize,
+    used: &mut [bool],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> bool {
+    let mut assigned = false;
+    for reference in action.references() {
+        let index = match resolve(rule, alternative, action, visible, reference) {
+            Ok(index) => index,
+            Err(diagnostic) => {
+                diagnostics.push(*diagnostic);
+                continue;
+            }
+        };
+        match index {
+            None if reference.data.kind == ReferenceKind::Value => assigned = true,
+            None => {}
+            Some(n) if n > visible as i32 => {
+                let label = if visible < alternative.elements.len() {
+                    format!("{} before this action", symbols(visible))
+                } else {
+                    format!("the alternative has {}", symbols(visible))
+                };
+                diagnostics.push(
+                    Diagnostic::error(
+                        format!("integer out of range: '{}'", reference.data),
+                        reference.span.clone(),
+                    )
+                    .with_code("integer-out-of-range")
+                    .with_primary_label(label)
+                    .with_secondary_label(alternative.span.clone(), String::new()),
+                )
+            }
+            Some(n) if n >= 1 && reference.data.kind == ReferenceKind::Value => {
+                used[n as usize] = true
+            }
+            Some(_) => {}
+        }
+    }
+    assigned
+}
+
+fn symbols(count: usize) -> String {
+    match count {
+        1 => "1 symbol".to_string(),
+        _ => format!("{} symbols", count),
+    }
+}
+
+// The position a reference refers to, None for $$ and @$. Named references
+// must name the rule or exactly one of the symbols the action sees, and
+// numbers must fit in an int.
+fn resolve(
+    rule: &Rule,
+    alternative: &Alternative,
+    action: &Action,
+    visible: usize,
+    reference: &Spanned<Reference>,
+) -> Result<Option<i32>, Box<Diagnostic>> {
+    let name = match &reference.data.target {
+        Target::Result => return Ok(None),
+        Target::Index(n) => return Ok(Some(*n)),
+        Target::OutOfRange(_) => {
+            return Err(Box::new(
+                Diagnostic::error(
+                    format!("integer out of range: '{}'", reference.data),
+                    reference.span.clone(),
+                )
+                .with_code("integer-out-of-range")
+                .with_primary_label("does not fit in an int".to_string()),
+            ))
+        }
+        Target::Name(name) | Target::BracketedName(name) => name,
+    };
+    let matches = rule.named_references(alternative, visible, name);
+    match matches.len() {
+        0 => Err(Box::new(
+            Diagnostic::error(
+                format!("invalid reference: '{}'", reference.data),
+                reference.span.clone(),
+            )
+            .with_code("invalid-reference")
+            .with_primary_label(format!("no symbol named {} in this rule", name))
+            .with_secondary_label(action.code.span.clone(), String::new()),
+        )),
+        1 => Ok(matches[0].0),
+        _ => {
+            let mut diagnostic = Diagnostic::error(
+                format!("ambiguous reference: '{}'", reference.data),
+                reference.span.clone(),
+            )
+            .with_code("ambiguous-reference")
+            .with_primary_label(format!("{} appears {} times", name, matches.len()));
+            for (index, span) in matches {
+                let label = match index {
+                    None => "refers to $$".to_string(),
+                    Some(n) => format!("refers to ${}", n),
+                };
+                diagnostic = diagnostic.with_secondary_label(span, label);
+            }
+            Err(Box::new(diagnostic))
+        }
+    }
+}
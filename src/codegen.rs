@@ -0,0 +1,632 @@
+// A Rust parser generated from a grammar whose actions are Rust code. The
+// generated module has a Token enum for the lexer to produce, a value stack
+// holding the semantic values, and a parse function driving the LALR(1)
+// tables of the grammar, with conflicts resolved like the tables do.
+//
+// The <type> of a symbol is a Rust type, or the name of a field of a
+// Rust-style %union { name: Type, ... }, which allows types like Box<Expr>
+// that cannot be written between angle brackets. Typed tokens carry their
+// value in the Token enum. Symbols without a type have the value ().
+//
+// $$ = ... in an action sets the value of the rule, and an action which
+// does not mention $$ gives its value as the value of the block. $n moves the
+// value of the n-th symbol, while $0 and below, as used by mid-rule actions,
+// clone the values left on the stack. Locations and error recovery are not
+// supported: a syntax error ends the parse.
+use crate::action::Reference;
+use crate::action::ReferenceKind;
+use crate::action::Target;
+use crate::diagnostics::Diagnostic;
+use crate::grammar::Alternative;
+use crate::grammar::Directive;
+use crate::grammar::Element;
+use crate::grammar::Grammar;
+use crate::grammar::Rule;
+use crate::lr::Automaton;
+use crate::symbols::Symbol;
+use crate::symbols::SymbolId;
+use crate::symbols::SymbolKind;
+use crate::symbols::SymbolTable;
+use crate::tables;
+use crate::tables::Tables;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// The Rust type of symbols without a type
+const UNIT: &str = "()";
+
+// Generates the parser module, or reports what prevents it. The grammar is
+// expected to have passed check_symbols and check_actions.
+pub fn generate(grammar: &Grammar) -> Result<String, Vec<Diagnostic>> {
+    let grammar = grammar.desugar_mid_rule_actions();
+    let (symbols, _) = SymbolTable::new(&grammar);
+    let Some(start) = symbols.start() else {
+        return Err(vec![Diagnostic::error(
+            "the grammar has no rules".to_string(),
+            grammar.epilogue.span.clone(),
+        )]);
+    };
+    let automaton = Automaton::lalr(&grammar, &symbols);
+    let tables = Tables::new(&automaton, &symbols);
+    let mut generator = Generator::new(&grammar, &symbols, &automaton, &tables);
+    generator.check_tokens();
+    let reduce = generator.reduce();
+    if !generator.diagnostics.is_empty() {
+        return Err(generator.diagnostics);
+    }
+
+    let mut out = String::new();
+    out.push_str("// Generated by yacc-parser. Do not edit.\n");
+    for prologue in &grammar.prologues {
+        out.push_str(&prologue.data);
+        out.push('\n');
+    }
+    generator.write_token(&mut out);
+    generator.write_error(&mut out);
+    generator.write_tables(&mut out);
+    generator.write_value(&mut out);
+    generator.write_parse(&mut out, start);
+    out.push_str(&reduce);
+    out.push_str(&grammar.epilogue.data);
+    Ok(out)
+}
+
+// What a reference in an action stands for
+enum Resolved {
+    Result,
+    // $n for one of the symbols of the alternative
+    Value(usize),
+    // $0 and below, with their type
+    Below(i32, String),
+}
+
+struct Generator<'a> {
+    grammar: &'a Grammar,
+    symbols: &'a SymbolTable,
+    automaton: &'a Automaton,
+    tables: &'a Tables,
+    // The fields of the %union, whose names can be used as types
+    fields: HashMap<String, String>,
+    // The Rust type of every symbol
+    types: Vec<String>,
+    // The distinct types of the values, other than (), in order
+    variants: Vec<String>,
+    // For the rule of each mid-rule action, its position in the enclosing
+    // alternative and the symbols before it
+    contexts: HashMap<SymbolId, (usize, Vec<SymbolId>)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(
+        grammar: &'a Grammar,
+        symbols: &'a SymbolTable,
+        automaton: &'a Automaton,
+        tables: &'a Tables,
+    ) -> Self {
+        let fields = union_fields(grammar);
+        let types = symbols
+            .symbols()
+            .map(|(_, symbol)| {
+                if symbol.name.starts_with("$@") {
+                    // The type given with $<type>$ in the action, if any
+                    let rule = &grammar.rules[symbol.rules[0]];
+                    rule.alternatives[0]
+                        .action
+                        .iter()
+                        .flat_map(|action| action.references())
+                        .find_map(|reference| match &reference.data {
+                            Reference {
+                                kind: ReferenceKind::Value,
+                                type_tag: Some(tag),
+                                target: Target::Result,
+                            } => Some(rust_type(&fields, tag)),
+                            _ => None,
+                        })
+                        .unwrap_or(UNIT.to_string())
+                } else if let SymbolKind::Char(_) = symbol.kind {
+                    UNIT.to_string()
+                } else {
+                    symbol
+                        .type_name
+                        .as_deref()
+                        .map_or(UNIT.to_string(), |tag| rust_type(&fields, tag))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut variants = Vec::new();
+        for rust_type in &types {
+            if rust_type != UNIT && !variants.contains(rust_type) {
+                variants.push(rust_type.clone());
+            }
+        }
+
+        let contexts = symbols.mid_rule_contexts(grammar);
+
+        Generator {
+            grammar,
+            symbols,
+            automaton,
+            tables,
+            fields,
+            types,
+            variants,
+            contexts,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    // Tokens other than characters become variants of the Token enum, so
+    // they need names which are Rust identifiers
+    fn check_tokens(&mut self) {
+        for (_, symbol) in self.tokens() {
+            if symbol.kind == SymbolKind::Terminal && !symbol.is_identifier() {
+                let span = symbol.span.clone().unwrap_or_default();
+                self.diagnostics.push(
+                    Diagnostic::error(
+                        format!("token {} has no name to use in the Token enum", symbol.name),
+                        span,
+                    )
+                    .with_help(format!(
+                        "declare it with a name, e.g. %token NAME {}",
+                        symbol.name
+                    )),
+                );
+            }
+        }
+    }
+
+    // The tokens the lexer can give, without $end and error
+    fn tokens(&self) -> impl Iterator<Item = (SymbolId, &'a Symbol)> {
+        self.symbols
+            .terminals()
+            .filter(|(id, _)| *id != SymbolTable::END && *id != SymbolTable::ERROR)
+    }
+
+    fn variant(&self, id: SymbolId) -> Option<usize> {
+        self.variants.iter().position(|t| *t == self.types[id.0])
+    }
+
+    fn write_token(&self, out: &mut String) {
+        out.push_str(
+            "\n#[allow(non_camel_case_types, clippy::upper_case_acronyms)]\n\
+             #[derive(Debug)]\n\
+             pub enum Token {\n",
+        );
+        for (id, symbol) in self.tokens() {
+            if symbol.kind != SymbolKind::Terminal {
+                continue;
+            }
+            match self.variant(id) {
+                Some(_) => writeln!(out, "    {}({}),", symbol.name, self.types[id.0]),
+                None => writeln!(out, "    {},", symbol.name),
+            }
+            .unwrap();
+        }
+        out.push_str("    // A character literal token such as '+'\n    Char(char),\n}\n\n");
+
+        out.push_str(
+            "impl Token {\n    \
+             // The index of the token in the tables, past the end of NAMES for\n    \
+             // characters which are not tokens of the grammar\n    \
+             fn kind(&self) -> usize {\n        \
+             match self {\n",
+        );
+        for (id, symbol) in self.tokens() {
+            match symbol.kind {
+                SymbolKind::Char(c) => {
+                    writeln!(out, "            Token::Char({:?}) => {},", c, id.0)
+                }
+                _ if self.variant(id).is_some() => {
+                    writeln!(out, "            Token::{}(_) => {},", symbol.name, id.0)
+                }
+                _ => writeln!(out, "            Token::{} => {},", symbol.name, id.0),
+            }
+            .unwrap();
+        }
+        out.push_str(
+            "            Token::Char(_) => usize::MAX,\n        }\n    }\n\n    \
+             fn into_value(self) -> Value {\n",
+        );
+        if !self.tokens().any(|(id, _)| self.variant(id).is_some()) {
+            out.push_str("        Value::None\n    }\n}\n");
+            return;
+        }
+        out.push_str("        match self {\n");
+        for (id, symbol) in self.tokens() {
+            if let Some(variant) = self.variant(id) {
+                writeln!(
+                    out,
+                    "            Token::{}(value) => Value::V{}(value),",
+                    symbol.name, variant
+                )
+                .unwrap();
+            }
+        }
+        out.push_str("            _ => Value::None,\n        }\n    }\n}\n");
+    }
+
+    fn write_error(&self, out: &mut String) {
+        out.push_str(
+            "\n#[derive(Debug)]\n\
+             pub struct SyntaxError {\n    \
+             // The unexpected token, None at the end of input\n    \
+             pub token: Option<Token>,\n    \
+             // The names of the tokens which could have come instead\n    \
+             pub expected: Vec<&'static str>,\n\
+             }\n\n\
+             impl std::fmt::Display for SyntaxError {\n    \
+             fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {\n        \
+             match &self.token {\n            \
+             // Characters the grammar does not use have no name\n            \
+             Some(token @ Token::Char(c)) if token.kind() == usize::MAX => {\n                \
+             write!(f, \"syntax error, unexpected {:?}\", c)?\n            \
+             }\n            \
+             Some(token) => write!(f, \"syntax error, unexpected {}\", NAMES[token.kind()])?,\n            \
+             None => write!(f, \"syntax error, unexpected end of input\")?,\n        \
+             }\n        \
+             if !self.expected.is_empty() {\n            \
+             write!(f, \", expecting {}\", self.expected.join(\" or \"))?;\n        \
+             }\n        \
+             Ok(())\n    \
+             }\n\
+             }\n\n\
+             impl std::error::Error for SyntaxError {}\n",
+        );
+    }
+
+    fn write_tables(&self, out: &mut String) {
+        out.push_str(
+            "\n// The names of the tokens, by their index in the tables\nconst NAMES: &[&str] = &[",
+        );
+        for (_, symbol) in self.symbols.terminals() {
+            write!(out, "{:?}, ", symbol.alias.as_ref().unwrap_or(&symbol.name)).unwrap();
+        }
+        out.push_str(
+            "];\n\n\
+             #[derive(Clone, Copy)]\n\
+             enum Act {\n    \
+             Shift(usize),\n    \
+             Reduce(usize),\n    \
+             Accept,\n\
+             }\n\n\
+             // The action of each state on the tokens which are not errors,\n\
+             // sorted by token\n\
+             static ACTION: &[&[(usize, Act)]] = &[\n",
+        );
+        for row in &self.tables.action {
+            out.push_str("    &[");
+            for (token, action) in row {
+                let action = match action {
+                    tables::Action::Shift(state) => format!("Act::Shift({})", state),
+                    tables::Action::Reduce(production) => format!("Act::Reduce({})", production),
+                    tables::Action::Accept => "Act::Accept".to_string(),
+                    tables::Action::Error => continue,
+                };
+                write!(out, "({}, {}), ", token.0, action).unwrap();
+            }
+            out.push_str("],\n");
+        }
+        out.push_str(
+            "];\n\n\
+             // The state after reducing to each nonterminal, sorted by nonterminal\n\
+             static GOTO: &[&[(usize, usize)]] = &[\n",
+        );
+        for row in &self.tables.goto {
+            out.push_str("    &[");
+            for (symbol, state) in row {
+                write!(out, "({}, {}), ", symbol.0, state).unwrap();
+            }
+            out.push_str("],\n");
+        }
+        out.push_str("];\n\n// The nonterminal and length of each production\nstatic PRODUCTIONS: &[(usize, usize)] = &[\n");
+        for production in &self.automaton.productions {
+            writeln!(out, "    ({}, {}),", production.lhs.0, production.rhs.len()).unwrap();
+        }
+        out.push_str("];\n");
+    }
+
+    fn write_value(&self, out: &mut String) {
+        out.push_str("\nenum Value {\n    None,\n");
+        for (i, rust_type) in self.variants.iter().enumerate() {
+            writeln!(out, "    V{}({}),", i, rust_type).unwrap();
+        }
+        out.push_str("}\n\n#[allow(dead_code)]\nimpl Value {\n    fn unit(self) {}\n");
+        for (i, rust_type) in self.variants.iter().enumerate() {
+            writeln!(
+                out,
+                "\n    fn v{i}(self) -> {t} {{\n        \
+                 match self {{\n            \
+                 Value::V{i}(value) => value,\n            \
+                 _ => unreachable!(),\n        \
+                 }}\n    \
+                 }}\n\n    \
+                 fn v{i}_ref(&self) -> &{t} {{\n        \
+                 match self {{\n            \
+                 Value::V{i}(value) => value,\n            \
+                 _ => unreachable!(),\n        \
+                 }}\n    \
+                 }}",
+                i = i,
+                t = rust_type
+            )
+            .unwrap();
+        }
+        out.push_str("}\n");
+    }
+
+    fn write_parse(&self, out: &mut String, start: SymbolId) {
+        let accept = match self.variant(start) {
+            Some(variant) => format!("v{}", variant),
+            None => "unit".to_string(),
+        };
+        write!(
+            out,
+            "
+// Parses the tokens of the lexer into the value of the start symbol
+pub fn parse<I: IntoIterator<Item = Token>>(lexer: I) -> Result<{t}, SyntaxError> {{
+    let mut lexer = lexer.into_iter();
+    let mut lookahead = lexer.next();
+    let mut states = vec![0];
+    let mut values = Vec::new();
+    loop {{
+        let state = *states.last().unwrap();
+        let kind = lookahead.as_ref().map_or(0, Token::kind);
+        let row = ACTION[state];
+        let Ok(i) = row.binary_search_by_key(&kind, |&(kind, _)| kind) else {{
+            return Err(SyntaxError {{
+                token: lookahead,
+                expected: row.iter().map(|&(kind, _)| NAMES[kind]).collect(),
+            }});
+        }};
+        match row[i].1 {{
+            Act::Shift(next) => {{
+                states.push(next);
+                values.push(lookahead.take().unwrap().into_value());
+                lookahead = lexer.next();
+            }}
+            Act::Reduce(production) => {{
+                let (lhs, len) = PRODUCTIONS[production];
+                states.truncate(states.len() - len);
+                let popped = values.split_off(values.len() - len);
+                let value = reduce(production, popped, &values);
+                let row = GOTO[*states.last().unwrap()];
+                let i = row.binary_search_by_key(&lhs, |&(lhs, _)| lhs).unwrap();
+                states.push(row[i].1);
+                values.push(value);
+            }}
+            Act::Accept => return Ok(values.pop().unwrap().{accept}()),
+        }}
+    }}
+}}
+",
+            t = self.types[start.0],
+            accept = accept
+        )
+        .unwrap();
+    }
+
+    // The function running the action of a production, given the values of
+    // its symbols and the values below them on the stack
+    fn reduce(&mut self) -> String {
+        let mut out = String::from(
+            "\n#[allow(unused_mut, unused_braces, clippy::let_unit_value)]\n\
+             fn reduce(production: usize, __values: Vec<Value>, __stack: &[Value]) -> Value {\n    \
+             let mut __values = __values.into_iter();\n    \
+             match production {\n",
+        );
+        for (p, production) in self.automaton.productions.iter().enumerate().skip(1) {
+            let rule = &self.grammar.rules[production.rule];
+            let alternative = &rule.alternatives[production.alternative];
+            writeln!(
+                out,
+                "        // {}: {}",
+                rule.name.data,
+                alternative_text(alternative)
+            )
+            .unwrap();
+            let body = self.production(production.lhs, &production.rhs, rule, alternative);
+            writeln!(out, "        {} => {{\n{}        }}", p, body).unwrap();
+        }
+        out.push_str("        _ => unreachable!(),\n    }\n}\n");
+        out
+    }
+
+    fn production(
+        &mut self,
+        lhs: SymbolId,
+        rhs: &[SymbolId],
+        rule: &Rule,
+        alternative: &Alternative,
+    ) -> String {
+        let lhs_type = self.types[lhs.0].clone();
+        let wrap = |value: &str| match self.variants.iter().position(|t| *t == lhs_type) {
+            Some(variant) => format!("Value::V{}({})", variant, value),
+            None => "Value::None".to_string(),
+        };
+        let mut out = String::new();
+        let Some(action) = &alternative.action else {
+            // The default action $$ = $1
+            let message = match rhs.first() {
+                _ if lhs_type == UNIT => None,
+                Some(first) if self.types[first.0] == lhs_type => None,
+                Some(first) => Some(format!(
+                    "type clash on default action: {} != {}",
+                    lhs_type, self.types[first.0]
+                )),
+                None => Some("empty rule for typed nonterminal, and no action".to_string()),
+            };
+            if let Some(message) = message {
+                self.diagnostics.push(
+                    Diagnostic::error(message, alternative.span.clone()).with_secondary_label(
+                        rule.name.span.clone(),
+                        format!("declared with type {}", lhs_type),
+                    ),
+                );
+            }
+            out.push_str("            __values.next().unwrap_or(Value::None)\n");
+            return out;
+        };
+
+        let mut used = vec![false; rhs.len() + 1];
+        let mut assigned = false;
+        for reference in action.references() {
+            match self.resolve(lhs, rhs.len(), rule, alternative, &reference.data) {
+                Ok(Resolved::Result) => assigned = true,
+                Ok(Resolved::Value(n)) => used[n] = true,
+                Ok(Resolved::Below(..)) => {}
+                Err(message) => self
+                    .diagnostics
+                    .push(Diagnostic::error(message, reference.span.clone())),
+            }
+        }
+        for (i, symbol) in rhs.iter().enumerate() {
+            match self.variant(*symbol) {
+                Some(variant) if used[i + 1] => writeln!(
+                    out,
+                    "            let mut __{} = __values.next().unwrap().v{}();",
+                    i + 1,
+                    variant
+                ),
+                _ => writeln!(out, "            __values.next();"),
+            }
+            .unwrap();
+        }
+        let code = action.rewrite(|reference| {
+            match self.resolve(lhs, rhs.len(), rule, alternative, reference) {
+                Ok(Resolved::Result) => Some("__result".to_string()),
+                Ok(Resolved::Value(n)) if self.types[rhs[n - 1].0] == UNIT => {
+                    Some(UNIT.to_string())
+                }
+                Ok(Resolved::Value(n)) => Some(format!("__{}", n)),
+                Ok(Resolved::Below(_, rust_type)) if rust_type == UNIT => Some(UNIT.to_string()),
+                Ok(Resolved::Below(n, rust_type)) => {
+                    let variant = self.variants.iter().position(|t| *t == rust_type)?;
+                    Some(format!(
+                        "__stack[__stack.len() - {}].v{}_ref().clone()",
+                        1 - n,
+                        variant
+                    ))
+                }
+                Err(_) => None,
+            }
+        });
+        match (assigned, lhs_type == UNIT) {
+            (true, true) => writeln!(
+                out,
+                "            let mut __result = ();\n            {}",
+                code
+            ),
+            (true, false) => writeln!(
+                out,
+                "            let mut __result: {};\n            {}",
+                lhs_type, code
+            ),
+            (false, true) => writeln!(out, "            {}", code),
+            (false, false) => writeln!(out, "            let __result: {} = {};", lhs_type, code),
+        }
+        .unwrap();
+        writeln!(out, "            {}", wrap("__result")).unwrap();
+        out
+    }
+
+    fn resolve(
+        &self,
+        lhs: SymbolId,
+        len: usize,
+        rule: &Rule,
+        alternative: &Alternative,
+        reference: &Reference,
+    ) -> Result<Resolved, String> {
+        if reference.kind == ReferenceKind::Location {
+            return Err(format!(
+                "{}: locations are not supported in Rust parsers",
+                reference
+            ));
+        }
+        let n = match &reference.target {
+            Target::Result => return Ok(Resolved::Result),
+            Target::Index(n) => *n,
+            Target::OutOfRange(_) => {
+                return Err(format!("integer out of range: {}", reference));
+            }
+            Target::Name(name) | Target::BracketedName(name) => {
+                match rule.named_references(alternative, len, name)[..] {
+                    [(None, _)] => return Ok(Resolved::Result),
+                    [(Some(n), _)] => n,
+                    _ => return Err(format!("invalid reference: {}", reference)),
+                }
+            }
+        };
+        if n > len as i32 {
+            return Err(format!("invalid reference: {}", reference));
+        }
+        if n >= 1 {
+            return Ok(Resolved::Value(n as usize));
+        }
+        if let Some(tag) = &reference.type_tag {
+            return Ok(Resolved::Below(n, rust_type(&self.fields, tag)));
+        }
+        // In a mid-rule action, the symbols before it in its alternative
+        match self.contexts.get(&lhs) {
+            Some((i, before)) if *i as i32 + n >= 1 => {
+                let symbol = before[(*i as i32 + n - 1) as usize];
+                Ok(Resolved::Below(n, self.types[symbol.0].clone()))
+            }
+            _ => Err(format!(
+                "the type of {} is unknown, give it with $<type>{}",
+                reference, n
+            )),
+        }
+    }
+}
+
+// The fields of a Rust-style %union { name: Type, ... }, whose names can be
+// used as types
+fn union_fields(grammar: &Grammar) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    for directive in &grammar.directives {
+        let Directive::Union { code } = &directive.data else {
+            continue;
+        };
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, c) in code.char_indices().chain([(code.len(), ',')]) {
+            match c {
+                '<' | '(' | '[' | '{' => depth += 1,
+                '>' | ')' | ']' | '}' => depth -= 1,
+                ',' | ';' if depth == 0 => {
+                    if let Some((name, rust_type)) = code[start..i].split_once(':') {
+                        fields.insert(name.trim().to_string(), rust_type.trim().to_string());
+                    }
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+    }
+    fields
+}
+
+// The Rust type of a <type>, through the fields of the %union
+fn rust_type(fields: &HashMap<String, String>, tag: &str) -> String {
+    let name = tag.trim_start_matches('<').trim_end_matches('>');
+    fields.get(name).cloned().unwrap_or(name.to_string())
+}
+
+// The symbols of an alternative, for comments
+fn alternative_text(alternative: &Alternative) -> String {
+    let text = alternative
+        .elements
+        .iter()
+        .filter(|e| !matches!(e, Element::Action(_)))
+        .map(|e| e.text().data.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if text.is_empty() {
+        "%empty".to_string()
+    } else {
+        text
+    }
+}
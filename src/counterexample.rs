@@ -0,0 +1,574 @@
+// Counterexamples for conflicts, in the style of Bison's -Wcounterexamples.
+// A conflict is explained by a derivation of each of its items from the
+// start symbol, both reading the same symbols up to the point of the
+// conflict. When the two derivations give the same symbols, the grammar is
+// ambiguous. Otherwise the conflict may only need more lookahead, and each
+// derivation comes with its own example.
+use crate::analysis::Analysis;
+use crate::grammar::Grammar;
+use crate::lr::Automaton;
+use crate::lr::Item;
+use crate::symbols::SymbolId;
+use crate::symbols::SymbolTable;
+use crate::tables::Conflict;
+use crate::tables::ConflictKind;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+// How many derivations of the second item to try to unify with the first
+const CANDIDATES: usize = 64;
+// How many steps the search for them may take
+const BUDGET: usize = 20_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Derivation {
+    Symbol(SymbolId),
+    // Where the parser is when the conflict comes up
+    Dot,
+    Node {
+        production: usize,
+        children: Vec<Derivation>,
+    },
+}
+
+impl Derivation {
+    // The symbols the derivation gives, None for the dot
+    pub fn leaves(&self) -> Vec<Option<SymbolId>> {
+        let mut leaves = Vec::new();
+        self.collect_leaves(&mut leaves);
+        leaves
+    }
+
+    fn collect_leaves(&self, leaves: &mut Vec<Option<SymbolId>>) {
+        match self {
+            Derivation::Symbol(symbol) => leaves.push(Some(*symbol)),
+            Derivation::Dot => leaves.push(None),
+            Derivation::Node { children, .. } => {
+                for child in children {
+                    child.collect_leaves(leaves);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Counterexample {
+    // The symbols read to reach the conflicting state
+    pub prefix: Vec<SymbolId>,
+    // A derivation of each item in conflict, the shift first for a
+    // shift/reduce conflict
+    pub derivations: [Derivation; 2],
+    // Whether both derivations give the same symbols, which shows that the
+    // grammar is ambiguous
+    pub unifying: bool,
+}
+
+// A step of a path through the items of the automaton
+type Step = (usize, Item);
+
+// A search node: a step, and whether the lookahead of the conflict has yet
+// to be found after it
+type Node = (usize, Item, bool);
+
+pub struct Explainer<'a> {
+    automaton: &'a Automaton,
+    symbols: &'a SymbolTable,
+    analysis: Analysis,
+    closures: Vec<HashSet<Item>>,
+    // The states with a transition to each state
+    predecessors: Vec<Vec<usize>>,
+    // A production deriving the empty string for each nullable nonterminal
+    empty: HashMap<SymbolId, usize>,
+    // A production deriving a string starting with the token, for each
+    // nonterminal and token in its FIRST set, keyed by nonterminal
+    starts: HashMap<SymbolId, HashMap<SymbolId, usize>>,
+}
+
+impl<'a> Explainer<'a> {
+    pub fn new(grammar: &Grammar, symbols: &'a SymbolTable, automaton: &'a Automaton) -> Self {
+        let closures = automaton
+            .states
+            .iter()
+            .map(|state| automaton.closure(&state.items).into_iter().collect())
+            .collect();
+        let mut predecessors = vec![Vec::new(); automaton.states.len()];
+        for (p, state) in automaton.states.iter().enumerate() {
+            for &q in state.transitions.values() {
+                if !predecessors[q].contains(&p) {
+                    predecessors[q].push(p);
+                }
+            }
+        }
+        let mut explainer = Explainer {
+            automaton,
+            symbols,
+            analysis: Analysis::new(grammar, symbols),
+            closures,
+            predecessors,
+            empty: HashMap::new(),
+            starts: HashMap::new(),
+        };
+        explainer.compute_witnesses();
+        explainer
+    }
+
+    // Picks the productions for `empty` and `starts` in the order the facts
+    // become known, so that expanding them always ends
+    fn compute_witnesses(&mut self) {
+        let productions = &self.automaton.productions;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, production) in productions.iter().enumerate().skip(1) {
+                if !self.empty.contains_key(&production.lhs)
+                    && production.rhs.iter().all(|s| self.empty.contains_key(s))
+                {
+                    self.empty.insert(production.lhs, i);
+                    changed = true;
+                }
+                for &symbol in &production.rhs {
+                    let tokens = if self.automaton.is_terminal(symbol) {
+                        vec![symbol]
+                    } else {
+                        self.starts
+                            .get(&symbol)
+                            .map_or_else(Vec::new, |starts| starts.keys().copied().collect())
+                    };
+                    let starts = self.starts.entry(production.lhs).or_default();
+                    for token in tokens {
+                        if let Entry::Vacant(entry) = starts.entry(token) {
+                            entry.insert(i);
+                            changed = true;
+                        }
+                    }
+                    if !self.empty.contains_key(&symbol) {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn explain(&self, conflict: &Conflict) -> Option<Counterexample> {
+        let q = conflict.state;
+        let reduce = |production: usize| Item {
+            production,
+            dot: self.automaton.productions[production].rhs.len(),
+        };
+        let first = match conflict.kind {
+            ConflictKind::ShiftReduce => *self.closures[q]
+                .iter()
+                .filter(|&&item| self.automaton.next_symbol(item) == Some(conflict.token))
+                .min()?,
+            ConflictKind::ReduceReduce => reduce(conflict.productions[0]),
+        };
+        let second = match conflict.kind {
+            ConflictKind::ShiftReduce => reduce(conflict.productions[0]),
+            ConflictKind::ReduceReduce => reduce(conflict.productions[1]),
+        };
+        let lookahead = |item: Item| {
+            let reducing = self.automaton.next_symbol(item).is_none();
+            reducing.then_some(conflict.token)
+        };
+
+        let mut fallback = None;
+        for (a, b, swapped) in [(first, second, false), (second, first, true)] {
+            let Some(path) = self.shortest_path(q, a, lookahead(a)) else {
+                continue;
+            };
+            let states = path_states(&path);
+            let one = self.derivation(&path, lookahead(a));
+            let mut candidates = self.constrained_paths(&states, b, lookahead(b));
+            candidates.sort_by_key(Vec::len);
+            let others = candidates
+                .iter()
+                .map(|path| self.derivation(path, lookahead(b)))
+                .collect::<Vec<_>>();
+            let unified = others.iter().find(|other| other.leaves() == one.leaves());
+            // When no derivation reads the same symbols, as happens when
+            // merging states made the conflict, the item gets its own
+            let other = match (unified, others.first()) {
+                (Some(other), _) | (None, Some(other)) => other.clone(),
+                (None, None) => match self.shortest_path(q, b, lookahead(b)) {
+                    Some(path) => self.derivation(&path, lookahead(b)),
+                    None => continue,
+                },
+            };
+            let (one, other) = trim(one, other);
+            let derivations = if swapped { [other, one] } else { [one, other] };
+            let counterexample = Counterexample {
+                prefix: prefix(self.automaton, &path),
+                derivations,
+                unifying: unified.is_some(),
+            };
+            if counterexample.unifying {
+                return Some(counterexample);
+            }
+            fallback.get_or_insert(counterexample);
+        }
+        fallback
+    }
+
+    // The name of a symbol, or $accept
+    fn name(&self, id: SymbolId) -> &str {
+        if id == self.automaton.accept() {
+            "$accept"
+        } else {
+            &self.symbols.symbol(id).name
+        }
+    }
+
+    // The symbols a derivation gives, e.g. exp '+' exp • '+' exp
+    pub fn example(&self, derivation: &Derivation) -> String {
+        derivation
+            .leaves()
+            .iter()
+            .map(|leaf| match leaf {
+                Some(symbol) => self.name(*symbol),
+                None => "•",
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // A derivation with the nested ones in brackets, e.g.
+    // exp → [ exp → exp '+' exp • ] '+' exp
+    pub fn render(&self, derivation: &Derivation) -> String {
+        match derivation {
+            Derivation::Symbol(symbol) => self.name(*symbol).to_string(),
+            Derivation::Dot => "•".to_string(),
+            Derivation::Node {
+                production,
+                children,
+            } => {
+                let mut out = format!(
+                    "{} →",
+                    self.name(self.automaton.productions[*production].lhs)
+                );
+                for child in children {
+                    out.push(' ');
+                    match child {
+                        Derivation::Node { .. } => {
+                            out.push_str(&format!("[ {} ]", self.render(child)))
+                        }
+                        _ => out.push_str(&self.render(child)),
+                    }
+                }
+                if children.is_empty() {
+                    out.push_str(" ε");
+                }
+                out
+            }
+        }
+    }
+
+    // The steps which lead to a search node: reading the symbol before the
+    // dot, or entering the item from one with its nonterminal after the dot.
+    // Entering it passes on the lookahead still to be found, unless what
+    // follows the nonterminal can start with it.
+    fn predecessors(&self, (q, item, pending): Node, lookahead: Option<SymbolId>) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let rhs = &self.automaton.productions[item.production].rhs;
+        if item.dot > 0 {
+            let symbol = rhs[item.dot - 1];
+            let previous = Item {
+                production: item.production,
+                dot: item.dot - 1,
+            };
+            for &p in &self.predecessors[q] {
+                if self.automaton.states[p].transitions.get(&symbol) == Some(&q)
+                    && self.closures[p].contains(&previous)
+                {
+                    nodes.push((p, previous, pending));
+                }
+            }
+            return nodes;
+        }
+        let lhs = self.automaton.productions[item.production].lhs;
+        let mut outer = self.closures[q]
+            .iter()
+            .filter(|&&outer| self.automaton.next_symbol(outer) == Some(lhs))
+            .copied()
+            .collect::<Vec<_>>();
+        outer.sort();
+        for outer in outer {
+            let rest = &self.automaton.productions[outer.production].rhs[outer.dot + 1..];
+            let pending = match (pending, lookahead) {
+                (true, Some(token)) => {
+                    let (first, nullable) = self.analysis.first_of(rest);
+                    if first.contains(&token) {
+                        false
+                    } else if nullable {
+                        true
+                    } else {
+                        continue;
+                    }
+                }
+                _ => false,
+            };
+            nodes.push((q, outer, pending));
+        }
+        nodes
+    }
+
+    fn is_start(&self, (q, item, pending): Node) -> bool {
+        q == 0 && item.production == 0 && item.dot == 0 && !pending
+    }
+
+    // The shortest path from the start to the item in state q, followed by
+    // the lookahead when there is one
+    fn shortest_path(
+        &self,
+        q: usize,
+        item: Item,
+        lookahead: Option<SymbolId>,
+    ) -> Option<Vec<Step>> {
+        let target = (q, item, lookahead.is_some());
+        let mut next = HashMap::<Node, Option<Node>>::from([(target, None)]);
+        let mut queue = VecDeque::from([target]);
+        while let Some(node) = queue.pop_front() {
+            if self.is_start(node) {
+                let mut path = Vec::new();
+                let mut current = Some(node);
+                while let Some(node) = current {
+                    path.push((node.0, node.1));
+                    current = next[&node];
+                }
+                return Some(path);
+            }
+            for predecessor in self.predecessors(node, lookahead) {
+                if let Entry::Vacant(entry) = next.entry(predecessor) {
+                    entry.insert(Some(node));
+                    queue.push_back(predecessor);
+                }
+            }
+        }
+        None
+    }
+
+    // Paths from the start to the item, reading symbols which lead through
+    // the given states
+    fn constrained_paths(
+        &self,
+        states: &[usize],
+        item: Item,
+        lookahead: Option<SymbolId>,
+    ) -> Vec<Vec<Step>> {
+        let mut search = Search {
+            explainer: self,
+            states,
+            lookahead,
+            path: Vec::new(),
+            on_path: HashSet::new(),
+            paths: Vec::new(),
+            budget: BUDGET,
+        };
+        search.visit(states.len() - 1, item, lookahead.is_some());
+        search.paths
+    }
+
+    // The derivation a path makes: each item entered is nested in the one
+    // before, and when the lookahead has to follow the conflict, what comes
+    // between derives the empty string and what follows starts with it
+    fn derivation(&self, path: &[Step], lookahead: Option<SymbolId>) -> Derivation {
+        // The items which enter another, and the last one
+        let mut items = Vec::new();
+        for (i, &(_, item)) in path.iter().enumerate() {
+            if path.get(i + 1).is_none_or(|&(_, next)| !reads(item, next)) {
+                items.push(item);
+            }
+        }
+        let mut pending = lookahead;
+        let mut derivation = None;
+        for item in items.into_iter().rev() {
+            let rhs = &self.automaton.productions[item.production].rhs;
+            let mut children = rhs[..item.dot]
+                .iter()
+                .map(|&s| Derivation::Symbol(s))
+                .collect::<Vec<_>>();
+            let rest = match derivation.take() {
+                Some(inner) => {
+                    children.push(inner);
+                    &rhs[item.dot + 1..]
+                }
+                None => {
+                    children.push(Derivation::Dot);
+                    &rhs[item.dot..]
+                }
+            };
+            match pending {
+                Some(token) => {
+                    let (first, _) = self.analysis.first_of(rest);
+                    if first.contains(&token) {
+                        children.extend(self.first_derivation(rest, token));
+                        pending = None;
+                    } else {
+                        children.extend(rest.iter().map(|&s| self.empty_derivation(s)));
+                    }
+                }
+                None => children.extend(rest.iter().map(|&s| Derivation::Symbol(s))),
+            }
+            derivation = Some(Derivation::Node {
+                production: item.production,
+                children,
+            });
+        }
+        derivation.unwrap()
+    }
+
+    fn empty_derivation(&self, symbol: SymbolId) -> Derivation {
+        let production = self.empty[&symbol];
+        Derivation::Node {
+            production,
+            children: self.automaton.productions[production]
+                .rhs
+                .iter()
+                .map(|&s| self.empty_derivation(s))
+                .collect(),
+        }
+    }
+
+    // Derivations of the symbols which make them start with the token
+    fn first_derivation(&self, symbols: &[SymbolId], token: SymbolId) -> Vec<Derivation> {
+        let mut derivations = Vec::new();
+        for (i, &symbol) in symbols.iter().enumerate() {
+            if symbol == token {
+                derivations.push(Derivation::Symbol(symbol));
+            } else if let Some(&production) = self
+                .starts
+                .get(&symbol)
+                .and_then(|starts| starts.get(&token))
+            {
+                let rhs = &self.automaton.productions[production].rhs;
+                derivations.push(Derivation::Node {
+                    production,
+                    children: self.first_derivation(rhs, token),
+                });
+            } else {
+                derivations.push(self.empty_derivation(symbol));
+                continue;
+            }
+            derivations.extend(symbols[i + 1..].iter().map(|&s| Derivation::Symbol(s)));
+            break;
+        }
+        derivations
+    }
+}
+
+// A depth-first search for paths through given states, backwards from the
+// end
+struct Search<'a, 'b> {
+    explainer: &'b Explainer<'a>,
+    states: &'b [usize],
+    lookahead: Option<SymbolId>,
+    // The steps from the end so far, with the position in `states`
+    path: Vec<(usize, Item)>,
+    on_path: HashSet<(usize, Item, bool)>,
+    paths: Vec<Vec<Step>>,
+    budget: usize,
+}
+
+impl Search<'_, '_> {
+    fn visit(&mut self, position: usize, item: Item, pending: bool) {
+        if self.paths.len() >= CANDIDATES || self.budget == 0 {
+            return;
+        }
+        self.budget -= 1;
+        let q = self.states[position];
+        self.path.push((position, item));
+        self.on_path.insert((position, item, pending));
+        if self.explainer.is_start((q, item, pending)) && position == 0 {
+            let path = self
+                .path
+                .iter()
+                .rev()
+                .map(|&(position, item)| (self.states[position], item))
+                .collect();
+            self.paths.push(path);
+        } else {
+            for (p, previous, pending) in self
+                .explainer
+                .predecessors((q, item, pending), self.lookahead)
+            {
+                let position = if previous.dot < item.dot {
+                    match position.checked_sub(1) {
+                        Some(position) if self.states[position] == p => position,
+                        _ => continue,
+                    }
+                } else {
+                    position
+                };
+                if !self.on_path.contains(&(position, previous, pending)) {
+                    self.visit(position, previous, pending);
+                }
+            }
+        }
+        self.path.pop();
+        self.on_path.remove(&(position, item, pending));
+    }
+}
+
+// Whether a step of a path reads a symbol rather than entering an item
+fn reads(item: Item, next: Item) -> bool {
+    next.production == item.production && next.dot == item.dot + 1
+}
+
+// The states a path goes through
+fn path_states(path: &[Step]) -> Vec<usize> {
+    let mut states = vec![path[0].0];
+    for window in path.windows(2) {
+        if reads(window[0].1, window[1].1) {
+            states.push(window[1].0);
+        }
+    }
+    states
+}
+
+// The symbols a path reads
+fn prefix(automaton: &Automaton, path: &[Step]) -> Vec<SymbolId> {
+    path.windows(2)
+        .filter(|w| reads(w[0].1, w[1].1))
+        .map(|w| automaton.productions[w[0].1.production].rhs[w[0].1.dot])
+        .collect()
+}
+
+// Leaves out the productions both derivations start with, down to where
+// they part
+fn trim(mut a: Derivation, mut b: Derivation) -> (Derivation, Derivation) {
+    loop {
+        let (
+            Derivation::Node {
+                production: pa,
+                children: ca,
+            },
+            Derivation::Node {
+                production: pb,
+                children: cb,
+            },
+        ) = (&a, &b)
+        else {
+            return (a, b);
+        };
+        if pa != pb || ca.len() != cb.len() {
+            return (a, b);
+        }
+        let differing = (0..ca.len())
+            .filter(|&i| ca[i] != cb[i])
+            .collect::<Vec<_>>();
+        let [i] = differing[..] else {
+            return (a, b);
+        };
+        if !matches!(ca[i], Derivation::Node { .. }) || !matches!(cb[i], Derivation::Node { .. }) {
+            return (a, b);
+        }
+        let (Derivation::Node { children: ca, .. }, Derivation::Node { children: cb, .. }) = (a, b)
+        else {
+            unreachable!();
+        };
+        a = ca.into_iter().nth(i).unwrap();
+        b = cb.into_iter().nth(i).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+// A lossless concrete syntax tree. Unlike Grammar it keeps every byte of the
+// input: whitespace and comments are stored as trivia on the neighbouring
+// tokens, so the original text can be reproduced exactly.
+use crate::error::ParseError;
+use crate::grammar::Grammar;
+use crate::grammar::Rule;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::token::Spanned;
+use crate::token::Token;
+use std::iter::Peekable;
+use std::ops::Range;
+
+// A Whitespace or Comment token
+pub type Trivia = Spanned<Token>;
+
+#[derive(Debug, Clone)]
+pub struct SyntaxToken {
+    pub kind: Token,
+    pub span: Range<usize>,
+    // Trivia since the end of the previous token's line
+    pub leading: Vec<Trivia>,
+    // Trivia following the token up to the end of its line
+    pub trailing: Vec<Trivia>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Grammar,
+    Directive,
+    Rule,
+    Alternative,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntaxNode {
+    pub kind: NodeKind,
+    pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    fn new(kind: NodeKind) -> Self {
+        SyntaxNode {
+            kind,
+            children: Vec::new(),
+        }
+    }
+
+    // All tokens below this node in source order
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'t>(&'t self, tokens: &mut Vec<&'t SyntaxToken>) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.collect_tokens(tokens),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntaxTree<'a> {
+    input: &'a str,
+    pub root: SyntaxNode,
+    // Trivia after the last token, only non-empty if there is no epilogue
+    pub trailing: Vec<Trivia>,
+}
+
+impl<'a> SyntaxTree<'a> {
+    pub fn parse(input: &'a str) -> Result<Self, ParseError> {
+        let (tokens, trailing) = attach_trivia(input, Lexer::with_trivia(input));
+        let stream = tokens
+            .iter()
+            .map(|t| Spanned::new(t.kind, t.span.clone()))
+            .collect::<Vec<_>>();
+        let grammar = Parser::new(input, stream.into_iter()).parse_grammar()?;
+
+        let mut tokens = tokens.into_iter().peekable();
+        let mut root = SyntaxNode::new(NodeKind::Grammar);
+        let mut directives = grammar.directives.iter().peekable();
+        let mut rules = grammar.rules.iter().peekable();
+        while let Some(token) = tokens.peek() {
+            let start = token.span.start;
+            if let Some(directive) = directives.next_if(|d| d.span.start == start) {
+                let mut node = SyntaxNode::new(NodeKind::Directive);
+                node.children = take_until(&mut tokens, directive.span.end);
+                root.children.push(SyntaxElement::Node(node));
+            } else if let Some(rule) = rules.next_if(|r| r.span.start == start) {
+                root.children
+                    .push(SyntaxElement::Node(rule_node(&mut tokens, rule)));
+            } else {
+                root.children
+                    .push(SyntaxElement::Token(tokens.next().unwrap()));
+            }
+        }
+
+        Ok(SyntaxTree {
+            input,
+            root,
+            trailing,
+        })
+    }
+
+    pub fn input(&self) -> &'a str {
+        self.input
+    }
+
+    pub fn text(&self, span: Range<usize>) -> &'a str {
+        &self.input[span]
+    }
+
+    // The AST for this tree
+    pub fn grammar(&self) -> Result<Grammar, ParseError> {
+        let stream = self
+            .root
+            .tokens()
+            .into_iter()
+            .map(|t| Spanned::new(t.kind, t.span.clone()))
+            .collect::<Vec<_>>();
+        Parser::new(self.input, stream.into_iter()).parse_grammar()
+    }
+}
+
+// Reproduces the input byte-for-byte
+impl std::fmt::Display for SyntaxTree<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for token in self.root.tokens() {
+            for trivia in &token.leading {
+                write!(f, "{}", &self.input[trivia.span.clone()])?;
+            }
+            write!(f, "{}", &self.input[token.span.clone()])?;
+            for trivia in &token.trailing {
+                write!(f, "{}", &self.input[trivia.span.clone()])?;
+            }
+        }
+        for trivia in &self.trailing {
+            write!(f, "{}", &self.input[trivia.span.clone()])?;
+        }
+        Ok(())
+    }
+}
+
+fn is_trivia(token: Token) -> bool {
+    matches!(token, Token::Whitespace | Token::Comment)
+}
+
+// Attaches trivia to the tokens: everything up to the end of a token's line
+// is trailing trivia, the rest is leading trivia of the next token. Trivia
+// after the last token is returned separately.
+fn attach_trivia(input: &str, lexer: Lexer) -> (Vec<SyntaxToken>, Vec<Trivia>) {
+    let mut tokens: Vec<SyntaxToken> = Vec::new();
+    let mut pending = Vec::new();
+    // Whether trivia still belongs to the line of the last token
+    let mut same_line = false;
+    for spanned in lexer {
+        if !is_trivia(spanned.data) {
+            tokens.push(SyntaxToken {
+                kind: spanned.data,
+                span: spanned.span,
+                leading: std::mem::take(&mut pending),
+                trailing: Vec::new(),
+            });
+            same_line = true;
+            continue;
+        }
+        if !same_line {
+            pending.push(spanned);
+            continue;
+        }
+        let trailing = &mut tokens.last_mut().unwrap().trailing;
+        match input[spanned.span.clone()].find('\n') {
+            Some(newline) if spanned.data == Token::Whitespace => {
+                let split = spanned.span.start + newline;
+                if split > spanned.span.start {
+                    trailing.push(Spanned::new(Token::Whitespace, spanned.span.start..split));
+                }
+                pending.push(Spanned::new(Token::Whitespace, split..spanned.span.end));
+                same_line = false;
+            }
+            _ => trailing.push(spanned),
+        }
+    }
+    (tokens, pending)
+}
+
+// The tokens not yet placed in the tree
+type Tokens = Peekable<std::vec::IntoIter<SyntaxToken>>;
+
+fn take_until(tokens: &mut Tokens, end: usize) -> Vec<SyntaxElement> {
+    let mut children = Vec::new();
+    while let Some(token) = tokens.next_if(|t| t.span.start < end) {
+        children.push(SyntaxElement::Token(token));
+    }
+    children
+}
+
+fn rule_node(tokens: &mut Tokens, rule: &Rule) -> SyntaxNode {
+    let mut node = SyntaxNode::new(NodeKind::Rule);
+    let mut alternatives = rule.alternatives.iter().peekable();
+    while let Some(token) = tokens.next_if(|t| t.span.start < rule.span.end) {
+        let start = token.span.start;
+        // Empty alternatives have an empty span at the following '|' or ';'
+        while alternatives
+            .next_if(|a| a.span.is_empty() && a.span.start == start)
+            .is_some()
+        {
+            node.children
+                .push(SyntaxElement::Node(SyntaxNode::new(NodeKind::Alternative)));
+        }
+        match alternatives.next_if(|a| a.span.start == start) {
+            Some(alternative) => {
+                let mut child = SyntaxNode::new(NodeKind::Alternative);
+                child.children.push(SyntaxElement::Token(token));
+                child
+                    .children
+                    .extend(take_until(tokens, alternative.span.end));
+                node.children.push(SyntaxElement::Node(child));
+            }
+            None => node.children.push(SyntaxElement::Token(token)),
+        }
+    }
+    node
+}
@@ -0,0 +1,346 @@
+use crate::error::ParseError;
+use std::fmt::Write;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelStyle {
+    // Underlined with '^', the place the diagnostic is about
+    Primary,
+    // Underlined with '-', related places giving context
+    Secondary,
+}
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub style: LabelStyle,
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+// A problem found in a grammar file, pointing at the offending source range.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // A stable name for the kind of problem, e.g. unused-token, shown after
+    // the severity
+    pub code: Option<&'static str>,
+    pub message: String,
+    // The primary span
+    pub span: Range<usize>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: String, span: Range<usize>) -> Self {
+        Diagnostic::new(Severity::Error, message, span)
+    }
+
+    pub fn warning(message: String, span: Range<usize>) -> Self {
+        Diagnostic::new(Severity::Warning, message, span)
+    }
+
+    fn new(severity: Severity, message: String, span: Range<usize>) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            message,
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    // Sets the text shown under the primary span.
+    pub fn with_primary_label(mut self, message: String) -> Self {
+        self.labels.push(Label {
+            style: LabelStyle::Primary,
+            span: self.span.clone(),
+            message,
+        });
+        self
+    }
+
+    pub fn with_secondary_label(mut self, span: Range<usize>, message: String) -> Self {
+        self.labels.push(Label {
+            style: LabelStyle::Secondary,
+            span,
+            message,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn with_help(mut self, help: String) -> Self {
+        self.help.push(help);
+        self
+    }
+
+    // All labels, including an unlabelled primary one if none was given.
+    fn all_labels(&self) -> Vec<Label> {
+        let mut labels = self.labels.clone();
+        if !labels.iter().any(|l| l.style == LabelStyle::Primary) {
+            labels.insert(
+                0,
+                Label {
+                    style: LabelStyle::Primary,
+                    span: self.span.clone(),
+                    message: String::new(),
+                },
+            );
+        }
+        labels
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(err: ParseError) -> Self {
+        let label = if err.expected.is_empty() {
+            String::new()
+        } else {
+            let expected = err
+                .expected
+                .iter()
+                .map(|t| format!("{:?}", t))
+                .collect::<Vec<_>>()
+                .join(" or ");
+            format!("expected {}", expected)
+        };
+        Diagnostic::error(err.message, err.token.span).with_primary_label(label)
+    }
+}
+
+// Maps byte offsets into a source text to line and column numbers. The line
+// starts are computed once so lookups are a binary search.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(input: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(input.match_indices('\n').map(|(i, _)| i + 1));
+        LineIndex {
+            line_starts,
+            len: input.len(),
+        }
+    }
+
+    // 0-based line containing the byte offset
+    pub fn line(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    // Byte range of a 0-based line, excluding the newline
+    pub fn line_span(&self, input: &str, line: usize) -> Range<usize> {
+        let start = self.line_starts[line];
+        let end = self.line_starts.get(line + 1).map_or(self.len, |&s| s - 1);
+        let end = if input[start..end].ends_with('\r') {
+            end - 1
+        } else {
+            end
+        };
+        start..end
+    }
+
+    // 1-based line and column (counted in characters) of a byte offset
+    pub fn line_col(&self, input: &str, offset: usize) -> (usize, usize) {
+        let line = self.line(offset);
+        let col = input[self.line_starts[line]..offset].chars().count();
+        (line + 1, col + 1)
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+const TAB_WIDTH: usize = 4;
+
+// Renders diagnostics as rustc-style snippets of the source they point into.
+pub struct Renderer<'a> {
+    file_name: &'a str,
+    input: &'a str,
+    index: LineIndex,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(file_name: &'a str, input: &'a str) -> Self {
+        Renderer {
+            file_name,
+            input,
+            index: LineIndex::new(input),
+            color: false,
+        }
+    }
+
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn line_index(&self) -> &LineIndex {
+        &self.index
+    }
+
+    fn paint(&self, out: &mut String, style: &str, text: &str) {
+        if self.color && !text.is_empty() {
+            out.push_str(style);
+            out.push_str(text);
+            out.push_str(RESET);
+        } else {
+            out.push_str(text);
+        }
+    }
+
+    fn severity_style(severity: Severity) -> (&'static str, &'static str) {
+        match severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+        }
+    }
+
+    // Display width of the start of a line up to a byte offset.
+    fn display_col(&self, line_start: usize, offset: usize) -> usize {
+        self.input[line_start..offset]
+            .chars()
+            .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+            .sum()
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut out = String::new();
+        let (name, color) = Self::severity_style(diagnostic.severity);
+
+        match diagnostic.code {
+            Some(code) => self.paint(&mut out, color, &format!("{}[{}]", name, code)),
+            None => self.paint(&mut out, color, name),
+        }
+        self.paint(&mut out, BOLD, &format!(": {}", diagnostic.message));
+        out.push('\n');
+
+        let labels = diagnostic.all_labels();
+        let last_line = labels
+            .iter()
+            .map(|l| self.index.line(l.span.end.min(self.index.len)))
+            .max()
+            .unwrap_or(0);
+        let gutter = (last_line + 1).to_string().len();
+        let pad = " ".repeat(gutter);
+
+        let start = diagnostic.span.start.min(self.index.len);
+        let (line, col) = self.index.line_col(self.input, start);
+        self.paint(&mut out, BLUE, &format!("{}-->", pad));
+        writeln!(out, " {}:{}:{}", self.file_name, line, col).unwrap();
+        self.paint(&mut out, BLUE, &format!("{} |", pad));
+        out.push('\n');
+
+        // Underlines per line: (line, start col, end col, style, message)
+        let mut marks = Vec::new();
+        for label in &labels {
+            let start = label.span.start.min(self.index.len);
+            let end = label.span.end.clamp(start, self.index.len);
+            let first = self.index.line(start);
+            let last = self.index.line(end.saturating_sub(1).max(start));
+            // Long spans only show their first two and their last line
+            for line in (first..=last).filter(|&l| l <= first + 1 || l == last) {
+                let span = self.index.line_span(self.input, line);
+                let from = start.max(span.start);
+                let to = end.min(span.end);
+                let col0 = self.display_col(span.start, from);
+                let col1 = self.display_col(span.start, to).max(col0 + 1);
+                let message = if line == last {
+                    label.message.clone()
+                } else {
+                    String::new()
+                };
+                marks.push((line, col0, col1, label.style, message));
+            }
+        }
+        marks.sort_by_key(|m| (m.0, m.1));
+
+        let mut lines = marks.iter().map(|m| m.0).collect::<Vec<_>>();
+        lines.dedup();
+        let mut previous = None;
+        for line in lines {
+            if let Some(previous) = previous {
+                if line > previous + 1 {
+                    self.paint(&mut out, BLUE, "...");
+                    out.push('\n');
+                }
+            }
+            previous = Some(line);
+
+            let span = self.index.line_span(self.input, line);
+            let text = self.input[span].replace('\t', &" ".repeat(TAB_WIDTH));
+            self.paint(&mut out, BLUE, &format!("{:>gutter$} |", line + 1));
+            if !text.is_empty() {
+                out.push(' ');
+                out.push_str(&text);
+            }
+            out.push('\n');
+
+            for (_, col0, col1, style, message) in marks.iter().filter(|m| m.0 == line) {
+                let (marker, color) = match style {
+                    LabelStyle::Primary => ('^', color),
+                    LabelStyle::Secondary => ('-', BLUE),
+                };
+                self.paint(&mut out, BLUE, &format!("{} |", pad));
+                out.push(' ');
+                out.push_str(&" ".repeat(*col0));
+                let mut underline = marker.to_string().repeat(col1 - col0);
+                if !message.is_empty() {
+                    underline.push(' ');
+                    underline.push_str(message);
+                }
+                self.paint(&mut out, color, &underline);
+                out.push('\n');
+            }
+        }
+
+        if !diagnostic.notes.is_empty() || !diagnostic.help.is_empty() {
+            self.paint(&mut out, BLUE, &format!("{} |", pad));
+            out.push('\n');
+        }
+        for note in &diagnostic.notes {
+            self.paint(&mut out, BLUE, &format!("{} =", pad));
+            self.paint(&mut out, BOLD, " note");
+            writeln!(out, ": {}", note).unwrap();
+        }
+        for help in &diagnostic.help {
+            self.paint(&mut out, BLUE, &format!("{} =", pad));
+            self.paint(&mut out, BOLD, " help");
+            writeln!(out, ": {}", help).unwrap();
+        }
+        out
+    }
+}
@@ -1,7 +1,13 @@
+use crate::action::Action;
+use crate::action::Reference;
+use crate::action::ReferenceKind;
+use crate::action::Target;
 use crate::grammar::Alternative;
 use crate::grammar::Directive;
+use crate::grammar::Element;
 use crate::grammar::Grammar;
 use crate::grammar::Rule;
+use crate::grammar::TokenDeclaration;
 
 impl std::fmt::Display for Grammar {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -15,24 +21,23 @@ impl std::fmt::Display for Grammar {
         for rule in &self.rules {
             writeln!(f, "{}", rule)?;
         }
-        writeln!(f, "%%")?;
-        for line in self.epilogue.lines() {
-            writeln!(f, "{}", line)?;
-        }
-        Ok(())
+        // The epilogue is everything after the second %%, so it is written
+        // verbatim
+        write!(f, "%%{}", self.epilogue.data)
     }
 }
 
 impl std::fmt::Display for Directive {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Directive::PureParser => writeln!(f, "%pure-parser"),
-            Directive::Expect { number } => writeln!(f, "%expect {}", number),
-            Directive::NamePrefix { prefix } => writeln!(f, "%name-prefix=\"{}\"", prefix),
-            Directive::Locations => writeln!(f, "%locations"),
-            Directive::ParseParam { params } => writeln!(f, "%parse-param {{ {} }}", params),
-            Directive::LexProgram { params } => writeln!(f, "%lex-param {{ {} }}", params),
-            Directive::Union { code } => writeln!(f, "%union {{ {} }}", code),
+            Directive::PureParser => write!(f, "%pure-parser"),
+            Directive::Expect { number } => write!(f, "%expect {}", number),
+            Directive::ExpectRr { number } => write!(f, "%expect-rr {}", number),
+            Directive::NamePrefix { prefix } => write!(f, "%name-prefix=\"{}\"", prefix),
+            Directive::Locations => write!(f, "%locations"),
+            Directive::ParseParam { params } => write!(f, "%parse-param {{{}}}", params),
+            Directive::LexProgram { params } => write!(f, "%lex-param {{{}}}", params),
+            Directive::Union { code } => write!(f, "%union {{{}}}", code),
             Directive::Type {
                 type_name,
                 rule_names,
@@ -41,7 +46,7 @@ impl std::fmt::Display for Directive {
                 for rule_name in rule_names {
                     write!(f, " {}", rule_name)?;
                 }
-                writeln!(f)
+                Ok(())
             }
             Directive::Token {
                 token_name,
@@ -54,28 +59,28 @@ impl std::fmt::Display for Directive {
                 for rule_name in rule_names {
                     write!(f, " {}", rule_name)?;
                 }
-                writeln!(f)
+                Ok(())
             }
             Directive::Left { rule_names } => {
                 write!(f, "%left")?;
                 for rule_name in rule_names {
                     write!(f, " {}", rule_name)?;
                 }
-                writeln!(f)
+                Ok(())
             }
             Directive::Right { rule_names } => {
                 write!(f, "%right")?;
                 for rule_name in rule_names {
                     write!(f, " {}", rule_name)?;
                 }
-                writeln!(f)
+                Ok(())
             }
             Directive::NonAssoc { rule_names } => {
                 write!(f, "%nonassoc")?;
                 for rule_name in rule_names {
                     write!(f, " {}", rule_name)?;
                 }
-                writeln!(f)
+                Ok(())
             }
         }
     }
@@ -83,11 +88,12 @@ impl std::fmt::Display for Directive {
 
 impl std::fmt::Display for Rule {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{}:", self.name)?;
-        for alternative in &self.alternatives {
-            writeln!(f, "    |{}", alternative)?;
+        writeln!(f, "{}", self.name)?;
+        for (i, alternative) in self.alternatives.iter().enumerate() {
+            let separator = if i == 0 { ':' } else { '|' };
+            writeln!(f, "    {}{}", separator, alternative)?;
         }
-        writeln!(f, ";")
+        writeln!(f, "    ;")
     }
 }
 
@@ -105,3 +111,44 @@ impl std::fmt::Display for Alternative {
         Ok(())
     }
 }
+
+impl std::fmt::Display for Element {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text())
+    }
+}
+
+impl std::fmt::Display for TokenDeclaration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(alias) = &self.alias {
+            write!(f, " {}", alias)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code)
+    }
+}
+
+impl std::fmt::Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ReferenceKind::Value => write!(f, "$")?,
+            ReferenceKind::Location => write!(f, "@")?,
+        }
+        if let Some(type_tag) = &self.type_tag {
+            write!(f, "{}", type_tag)?;
+        }
+        match &self.target {
+            Target::Result => write!(f, "$"),
+            Target::Index(index) => write!(f, "{}", index),
+            Target::OutOfRange(digits) => write!(f, "{}", digits),
+            Target::Name(name) => write!(f, "{}", name),
+            Target::BracketedName(name) => write!(f, "[{}]", name),
+        }
+    }
+}
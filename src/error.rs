@@ -0,0 +1,32 @@
+use crate::token::Spanned;
+use crate::token::Token;
+
+// An error raised by the parser when the token stream does not match the
+// grammar of a yacc file.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    // The offending token
+    pub token: Spanned<Token>,
+    // The tokens which would have been accepted instead, empty if the error
+    // is not about an unexpected token (e.g. an unknown directive).
+    pub expected: Vec<Token>,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(token: Spanned<Token>, expected: Vec<Token>, message: String) -> Self {
+        ParseError {
+            token,
+            expected,
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.token.span.start)
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,72 @@
+// Decoding of C character and string literals as accepted by the lexer.
+
+// Decodes the escape sequences in the body of a literal (without quotes).
+// Returns None for an invalid escape or a value which is not a character.
+pub fn unescape(body: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let decoded = match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'a' => '\x07',
+            'b' => '\x08',
+            'f' => '\x0c',
+            'v' => '\x0b',
+            'e' => '\x1b',
+            c @ ('\\' | '\'' | '"' | '?') => c,
+            c @ '0'..='7' => {
+                let mut value = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                char::from_u32(value)?
+            }
+            'x' => {
+                let mut value: u32 = 0;
+                let mut digits = 0;
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(16)) {
+                    value = value.checked_mul(16)?.checked_add(digit)?;
+                    digits += 1;
+                    chars.next();
+                }
+                if digits == 0 {
+                    return None;
+                }
+                char::from_u32(value)?
+            }
+            _ => return None,
+        };
+        out.push(decoded);
+    }
+    Some(out)
+}
+
+// The string denoted by a string literal such as "a\"b", including its
+// quotes.
+pub fn string_value(literal: &str) -> Option<String> {
+    unescape(literal.strip_prefix('"')?.strip_suffix('"')?)
+}
+
+// The character denoted by a character literal such as 'a' or '\n',
+// including its quotes.
+pub fn char_value(literal: &str) -> Option<char> {
+    let body = literal.strip_prefix('\'')?.strip_suffix('\'')?;
+    let value = unescape(body)?;
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
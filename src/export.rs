@@ -0,0 +1,384 @@
+// Grammars exported for tools written in other languages.
+//
+// With the serde feature a grammar is written as JSON, wrapped in a document
+// with the version of the schema:
+//
+//   { "version": 1, "grammar": { "directives": [...], "rules": [...], ... } }
+//
+// The grammar mirrors the syntax tree. Every spanned value is an object
+// `{ "data": ..., "span": { "start": 3, "end": 8 } }` with byte offsets into
+// the source, and enum values carry their variant name in "kind", e.g.
+// `{ "kind": "Symbol", "data": "expr", "span": ... }`.
+//
+// For documentation the rules can be written as EBNF, in the W3C or the ISO
+// dialect. Actions and precedence are dropped, and the recursion yacc uses
+// for lists is turned back into repetition, e.g. `list: list ',' item | item`
+// becomes `list ::= item (',' item)*`.
+use crate::grammar::Directive;
+use crate::grammar::Element;
+use crate::grammar::Grammar;
+use std::collections::HashMap;
+
+// Bumped whenever the JSON of a grammar changes shape
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Document {
+    pub version: u32,
+    pub grammar: Grammar,
+}
+
+#[cfg(feature = "serde")]
+pub fn to_json(grammar: &Grammar) -> String {
+    let document = Document {
+        version: SCHEMA_VERSION,
+        grammar: grammar.clone(),
+    };
+    serde_json::to_string_pretty(&document).expect("grammars serialize to JSON")
+}
+
+// Reads back an exported grammar, refusing documents written with another
+// version of the schema.
+#[cfg(feature = "serde")]
+pub fn from_json(json: &str) -> Result<Grammar, String> {
+    // The version is checked first, as the grammar of another version may
+    // not even deserialize
+    #[derive(serde::Deserialize)]
+    struct Version {
+        version: u32,
+    }
+    let Version { version } = serde_json::from_str(json).map_err(|err| err.to_string())?;
+    if version != SCHEMA_VERSION {
+        return Err(format!(
+            "unsupported schema version {}, expected {}",
+            version, SCHEMA_VERSION
+        ));
+    }
+    let document: Document = serde_json::from_str(json).map_err(|err| err.to_string())?;
+    Ok(document.grammar)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    // As in the XML specification: a ::= b c? (d | e)*
+    W3c,
+    // ISO/IEC 14977: a = b, [ c ], { d | e } ;
+    Iso,
+}
+
+// The right-hand side of a rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Symbol(String),
+    // The text of a literal, which may contain any character
+    Terminal(String),
+    Sequence(Vec<Expr>),
+    Choice(Vec<Expr>),
+    Optional(Box<Expr>),
+    Plus(Box<Expr>),
+    Star(Box<Expr>),
+}
+
+// How tightly an expression binds, to know when it needs parentheses
+const CHOICE: u8 = 0;
+const SEQUENCE: u8 = 1;
+const POSTFIX: u8 = 2;
+const ATOM: u8 = 3;
+
+pub fn to_ebnf(grammar: &Grammar, dialect: Dialect) -> String {
+    // Tokens declared with an alias are written as the alias, which is how
+    // they appear in the language
+    let mut aliases = HashMap::new();
+    for directive in &grammar.directives {
+        if let Directive::Token { rule_names, .. } = &directive.data {
+            for declaration in rule_names {
+                if let Some(alias) = declaration.alias_value() {
+                    aliases.insert(declaration.name.data.as_str(), alias);
+                }
+            }
+        }
+    }
+
+    // Rules with the same name add alternatives to the same nonterminal
+    let mut names = Vec::new();
+    let mut alternatives = HashMap::<&str, Vec<Vec<Expr>>>::new();
+    for rule in &grammar.rules {
+        let name = rule.name.data.as_str();
+        if !alternatives.contains_key(name) {
+            names.push(name);
+        }
+        let entry = alternatives.entry(name).or_default();
+        for alternative in &rule.alternatives {
+            entry.push(
+                alternative
+                    .elements
+                    .iter()
+                    .filter_map(|element| match element {
+                        Element::Symbol(symbol) => Some(match aliases.get(symbol.data.as_str()) {
+                            Some(alias) => Expr::Terminal(alias.clone()),
+                            None => Expr::Symbol(symbol.data.clone()),
+                        }),
+                        Element::Char { value, .. } => Some(Expr::Terminal(value.to_string())),
+                        Element::String { value, .. } => Some(Expr::Terminal(value.clone())),
+                        Element::Action(_) => None,
+                    })
+                    .collect(),
+            );
+        }
+    }
+
+    let mut out = String::new();
+    for (i, name) in names.into_iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let body = lift(name, alternatives.remove(name).unwrap());
+        write_rule(&mut out, name, &body, dialect);
+    }
+    out
+}
+
+// The alternatives of a rule as one expression, with an empty alternative
+// made into an optional part and left or right recursion into repetition:
+//
+//   a: a X | Y    ->  Y X*
+//   a: X a | Y    ->  X* Y
+//   a: a X | X    ->  X+
+//   a: a S X | X  ->  X (S X)*
+fn lift(name: &str, alternatives: Vec<Vec<Expr>>) -> Expr {
+    let symbol = Expr::Symbol(name.to_string());
+    let (empty, alternatives): (Vec<_>, Vec<_>) =
+        alternatives.into_iter().partition(|a| a.is_empty());
+    let is_optional = !empty.is_empty();
+
+    for left in [true, false] {
+        let (recursive, bases): (Vec<_>, Vec<_>) = alternatives.iter().cloned().partition(|a| {
+            a.len() > 1
+                && if left {
+                    a[0] == symbol
+                } else {
+                    a[a.len() - 1] == symbol
+                }
+        });
+        // Without a way to end the recursion the rule matches nothing, which
+        // is best left as written
+        if recursive.is_empty() || (bases.is_empty() && !is_optional) {
+            continue;
+        }
+        let tails = recursive
+            .into_iter()
+            .map(|a| {
+                if left {
+                    a[1..].to_vec()
+                } else {
+                    a[..a.len() - 1].to_vec()
+                }
+            })
+            .collect::<Vec<_>>();
+        return repetition(tails, bases, is_optional, left);
+    }
+
+    if alternatives.is_empty() {
+        return Expr::Sequence(Vec::new());
+    }
+    let body = choice(alternatives.into_iter().map(sequence).collect());
+    if is_optional {
+        optional(body)
+    } else {
+        body
+    }
+}
+
+// a: a T1 | a T2 | B1 | B2 is (B1 | B2) (T1 | T2)*, and the right recursive
+// a: T1 a | T2 a | B1 | B2 is (T1 | T2)* (B1 | B2). An empty alternative is
+// one more base.
+fn repetition(tails: Vec<Vec<Expr>>, bases: Vec<Vec<Expr>>, is_optional: bool, left: bool) -> Expr {
+    if let ([tail], [base]) = (&tails[..], &bases[..]) {
+        let separator = if left {
+            tail.strip_suffix(&base[..])
+        } else {
+            tail.strip_prefix(&base[..])
+        };
+        match separator {
+            Some([]) if is_optional => return Expr::Star(Box::new(sequence(base.clone()))),
+            Some([]) => return Expr::Plus(Box::new(sequence(base.clone()))),
+            // The list may end with a separator when it is optional on the
+            // right, so only the required list can be written this way
+            Some(separator) if left || !is_optional => {
+                let base = sequence(base.clone());
+                let mut item = separator.to_vec();
+                item.push(base.clone());
+                let first = if is_optional { optional(base) } else { base };
+                return sequence(vec![first, Expr::Star(Box::new(sequence(item)))]);
+            }
+            _ => {}
+        }
+    }
+
+    let repeated = Expr::Star(Box::new(choice(tails.into_iter().map(sequence).collect())));
+    if bases.is_empty() {
+        return repeated;
+    }
+    let mut base = choice(bases.into_iter().map(sequence).collect());
+    if is_optional {
+        base = optional(base);
+    }
+    if left {
+        sequence(vec![base, repeated])
+    } else {
+        sequence(vec![repeated, base])
+    }
+}
+
+fn sequence(items: Vec<Expr>) -> Expr {
+    let mut flat = Vec::new();
+    for item in items {
+        match item {
+            Expr::Sequence(items) => flat.extend(items),
+            item => flat.push(item),
+        }
+    }
+    if flat.len() == 1 {
+        flat.pop().unwrap()
+    } else {
+        Expr::Sequence(flat)
+    }
+}
+
+fn choice(mut alternatives: Vec<Expr>) -> Expr {
+    if alternatives.len() == 1 {
+        alternatives.pop().unwrap()
+    } else {
+        Expr::Choice(alternatives)
+    }
+}
+
+fn optional(expr: Expr) -> Expr {
+    match expr {
+        Expr::Optional(_) | Expr::Star(_) => expr,
+        Expr::Plus(expr) => Expr::Star(expr),
+        expr => Expr::Optional(Box::new(expr)),
+    }
+}
+
+// Writes the rule with its top-level alternatives on separate lines
+fn write_rule(out: &mut String, name: &str, body: &Expr, dialect: Dialect) {
+    let (defines, indent) = match dialect {
+        Dialect::W3c => (" ::= ", name.len() + 3),
+        Dialect::Iso => (" = ", name.len() + 1),
+    };
+    out.push_str(name);
+    out.push_str(defines);
+    match body {
+        Expr::Choice(alternatives) => {
+            for (i, alternative) in alternatives.iter().enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                    out.push_str(&" ".repeat(indent));
+                    out.push_str("| ");
+                }
+                out.push_str(&render(alternative, dialect, SEQUENCE));
+            }
+        }
+        body => out.push_str(&render(body, dialect, CHOICE)),
+    }
+    if dialect == Dialect::Iso {
+        out.push_str(" ;");
+    }
+    out.push('\n');
+}
+
+// The expression where it must bind at least as tightly as `context`
+fn render(expr: &Expr, dialect: Dialect, context: u8) -> String {
+    let separator = match dialect {
+        Dialect::W3c => " ",
+        Dialect::Iso => ", ",
+    };
+    let (text, precedence) = match (expr, dialect) {
+        (Expr::Symbol(name), _) => (name.clone(), ATOM),
+        (Expr::Terminal(value), _) => {
+            let pieces = literal(value, dialect);
+            let precedence = if pieces.len() == 1 { ATOM } else { SEQUENCE };
+            (pieces.join(separator), precedence)
+        }
+        // Neither dialect can write the empty sequence but as a comment
+        (Expr::Sequence(items), Dialect::W3c) if items.is_empty() => {
+            ("/* empty */".to_string(), ATOM)
+        }
+        (Expr::Sequence(items), Dialect::Iso) if items.is_empty() => {
+            ("(* empty *)".to_string(), ATOM)
+        }
+        (Expr::Sequence(items), _) => {
+            let items = items
+                .iter()
+                .map(|item| match item {
+                    // The pieces of a literal are a sequence already
+                    Expr::Terminal(_) => render(item, dialect, SEQUENCE),
+                    item => render(item, dialect, POSTFIX),
+                })
+                .collect::<Vec<_>>();
+            (items.join(separator), SEQUENCE)
+        }
+        (Expr::Choice(alternatives), _) => {
+            let alternatives = alternatives
+                .iter()
+                .map(|alternative| render(alternative, dialect, SEQUENCE))
+                .collect::<Vec<_>>();
+            (alternatives.join(" | "), CHOICE)
+        }
+        (Expr::Optional(expr), Dialect::W3c) => {
+            (format!("{}?", render(expr, dialect, ATOM)), POSTFIX)
+        }
+        (Expr::Plus(expr), Dialect::W3c) => (format!("{}+", render(expr, dialect, ATOM)), POSTFIX),
+        (Expr::Star(expr), Dialect::W3c) => (format!("{}*", render(expr, dialect, ATOM)), POSTFIX),
+        (Expr::Optional(expr), Dialect::Iso) => {
+            (format!("[ {} ]", render(expr, dialect, CHOICE)), ATOM)
+        }
+        (Expr::Star(expr), Dialect::Iso) => {
+            (format!("{{ {} }}", render(expr, dialect, CHOICE)), ATOM)
+        }
+        // ISO EBNF has no one-or-more repetition
+        (Expr::Plus(expr), Dialect::Iso) => {
+            let repeated = sequence(vec![(**expr).clone(), Expr::Star(expr.clone())]);
+            return render(&repeated, dialect, context);
+        }
+    };
+    if precedence < context {
+        format!("({})", text)
+    } else {
+        text
+    }
+}
+
+// A terminal as quoted strings. Neither dialect has escapes, so the text is
+// split where it contains both kinds of quote, and control characters are
+// written as code points.
+fn literal(value: &str, dialect: Dialect) -> Vec<String> {
+    fn flush(pieces: &mut Vec<String>, current: &mut String) {
+        if !current.is_empty() {
+            let quote = if current.contains('\'') { '"' } else { '\'' };
+            pieces.push(format!("{}{}{}", quote, current, quote));
+            current.clear();
+        }
+    }
+
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    for c in value.chars() {
+        if c.is_control() {
+            flush(&mut pieces, &mut current);
+            pieces.push(match dialect {
+                Dialect::W3c => format!("#x{:X}", c as u32),
+                Dialect::Iso => format!("? U+{:04X} ?", c as u32),
+            });
+            continue;
+        }
+        if (c == '\'' && current.contains('"')) || (c == '"' && current.contains('\'')) {
+            flush(&mut pieces, &mut current);
+        }
+        current.push(c);
+    }
+    flush(&mut pieces, &mut current);
+    pieces
+}
@@ -0,0 +1,484 @@
+// yacc-fmt: lays out a grammar in a configurable style. Comments can only be
+// kept when formatting from source, as Grammar does not store them.
+use crate::cst::SyntaxTree;
+use crate::error::ParseError;
+use crate::grammar::Alternative;
+use crate::grammar::Directive;
+use crate::grammar::Grammar;
+use crate::grammar::Rule;
+use crate::token::Spanned;
+use crate::token::Token;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirstAlternative {
+    // name
+    //     : a
+    //     | b
+    //     ;
+    Colon,
+    // name: a
+    //     | b
+    //     ;
+    SameLine,
+    // name:
+    //       a
+    //     | b
+    //     ;
+    // An empty first alternative is left out, the '|' of the second one
+    // brings it back
+    Bar,
+}
+
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    // Number of spaces alternatives are indented by
+    pub indent: usize,
+    // Start all actions of a rule in the same column
+    pub align_actions: bool,
+    pub first_alternative: FirstAlternative,
+    // Alternatives longer than this are wrapped, 0 disables wrapping
+    pub max_width: usize,
+    // Keep comments when formatting source text
+    pub preserve_comments: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent: 4,
+            align_actions: false,
+            first_alternative: FirstAlternative::Colon,
+            max_width: 100,
+            preserve_comments: true,
+        }
+    }
+}
+
+pub fn format(grammar: &Grammar, options: &FormatOptions) -> String {
+    Formatter::new(options, Vec::new()).format(grammar)
+}
+
+// Formats the source text of a grammar, keeping its comments if
+// `preserve_comments` is set.
+pub fn format_source(input: &str, options: &FormatOptions) -> Result<String, ParseError> {
+    let tree = SyntaxTree::parse(input)?;
+    let grammar = tree.grammar()?;
+    let comments = if options.preserve_comments {
+        collect_comments(&tree)
+    } else {
+        Vec::new()
+    };
+    Ok(Formatter::new(options, comments).format(&grammar))
+}
+
+#[derive(Debug, Clone)]
+struct Comment {
+    text: String,
+    span: Range<usize>,
+    // Start of the token the comment is attached to
+    anchor: usize,
+    // Whether the comment is on its own line before its token
+    leading: bool,
+}
+
+impl Comment {
+    fn is_line(&self) -> bool {
+        self.text.starts_with("//")
+    }
+}
+
+fn collect_comments(tree: &SyntaxTree) -> Vec<Comment> {
+    let mut comments = Vec::new();
+    for token in tree.root.tokens() {
+        for (trivia, leading) in token
+            .leading
+            .iter()
+            .map(|t| (t, true))
+            .chain(token.trailing.iter().map(|t| (t, false)))
+        {
+            if trivia.data == Token::Comment {
+                comments.push(Comment {
+                    text: tree.text(trivia.span.clone()).to_string(),
+                    span: trivia.span.clone(),
+                    anchor: token.span.start,
+                    leading,
+                });
+            }
+        }
+    }
+    for trivia in &tree.trailing {
+        if trivia.data == Token::Comment {
+            comments.push(Comment {
+                text: tree.text(trivia.span.clone()).to_string(),
+                span: trivia.span.clone(),
+                anchor: tree.input().len(),
+                leading: true,
+            });
+        }
+    }
+    comments
+}
+
+// Comments around a line of output
+#[derive(Default)]
+struct LineComments {
+    // Printed on their own lines before the line
+    before: Vec<String>,
+    // Printed at the end of the line
+    after: Vec<String>,
+}
+
+impl LineComments {
+    fn split(comments: Vec<Comment>) -> Self {
+        let mut line = LineComments::default();
+        for comment in comments {
+            if comment.leading {
+                line.before.push(comment.text);
+            } else {
+                line.after.push(comment.text);
+            }
+        }
+        line.fix_line_comments();
+        line
+    }
+
+    // A `//` comment runs to the end of the line, so only one of them can
+    // be placed after the line and it has to come last.
+    fn fix_line_comments(&mut self) {
+        let (mut line, block): (Vec<_>, Vec<_>) =
+            self.after.drain(..).partition(|c| c.starts_with("//"));
+        self.after = block;
+        if let Some(last) = line.pop() {
+            self.before.extend(line);
+            self.after.push(last);
+        }
+    }
+}
+
+struct Formatter<'a> {
+    options: &'a FormatOptions,
+    comments: Vec<Comment>,
+    // Index of the first comment not yet placed
+    next_comment: usize,
+    out: String,
+}
+
+impl<'a> Formatter<'a> {
+    fn new(options: &'a FormatOptions, mut comments: Vec<Comment>) -> Self {
+        comments.sort_by_key(|c| (c.anchor, c.span.start));
+        Formatter {
+            options,
+            comments,
+            next_comment: 0,
+            out: String::new(),
+        }
+    }
+
+    // Takes the comments attached to tokens starting before `end`
+    fn take_comments(&mut self, end: usize) -> Vec<Comment> {
+        let start = self.next_comment;
+        while self.next_comment < self.comments.len()
+            && self.comments[self.next_comment].anchor < end
+        {
+            self.next_comment += 1;
+        }
+        self.comments[start..self.next_comment].to_vec()
+    }
+
+    fn indent(&self) -> String {
+        " ".repeat(self.options.indent)
+    }
+
+    fn line(&mut self, indent: &str, text: &str, comments: &LineComments) {
+        for comment in &comments.before {
+            self.out.push_str(indent);
+            self.out.push_str(comment);
+            self.out.push('\n');
+        }
+        self.out.push_str(indent);
+        self.out.push_str(text);
+        for comment in &comments.after {
+            self.out.push(' ');
+            self.out.push_str(comment);
+        }
+        self.out.push('\n');
+    }
+
+    // Lays out words after a prefix, wrapping onto continuation lines
+    // aligned with the first word.
+    fn wrap(&self, prefix: &str, words: Vec<String>) -> Vec<String> {
+        let continuation = " ".repeat(width(prefix));
+        // A prefix of only spaces is the indentation of the first word
+        let first = match prefix.trim_end() {
+            "" => prefix,
+            trimmed => trimmed,
+        };
+        let mut lines = vec![first.to_string()];
+        for (i, word) in words.into_iter().enumerate() {
+            let last = lines.last_mut().unwrap();
+            let fits = self.options.max_width == 0
+                || width(last) + 1 + width(&word) <= self.options.max_width;
+            if i == 0 || fits {
+                if !last.is_empty() && !last.ends_with(' ') {
+                    last.push(' ');
+                }
+                last.push_str(&word);
+            } else {
+                lines.push(format!("{}{}", continuation, word));
+            }
+        }
+        lines
+    }
+
+    // Symbol lists are wrapped, other directives are kept on one line
+    fn format_directive(&self, directive: &Directive) -> String {
+        let strings = |names: &[Spanned<String>]| names.iter().map(|n| n.to_string()).collect();
+        let (prefix, words): (String, Vec<String>) = match directive {
+            Directive::Type {
+                type_name,
+                rule_names,
+            } => (format!("%type {}", type_name), strings(rule_names)),
+            Directive::Token {
+                token_name,
+                rule_names,
+            } => {
+                let prefix = match token_name {
+                    Some(token_name) => format!("%token {}", token_name),
+                    None => "%token".to_string(),
+                };
+                // A token stays on the same line as its alias
+                (prefix, rule_names.iter().map(|t| t.to_string()).collect())
+            }
+            Directive::Left { rule_names } => ("%left".to_string(), strings(rule_names)),
+            Directive::Right { rule_names } => ("%right".to_string(), strings(rule_names)),
+            Directive::NonAssoc { rule_names } => ("%nonassoc".to_string(), strings(rule_names)),
+            _ => return directive.to_string(),
+        };
+        let mut lines = self.wrap(&format!("{} ", prefix), words);
+        for line in lines.iter_mut().skip(1) {
+            *line = format!("{}{}", self.indent(), line.trim_start());
+        }
+        lines.join("\n")
+    }
+
+    fn format(mut self, grammar: &Grammar) -> String {
+        // Directives and prologues in source order
+        let mut head = grammar
+            .directives
+            .iter()
+            .map(|d| (d.span.clone(), self.format_directive(&d.data)))
+            .chain(
+                grammar
+                    .prologues
+                    .iter()
+                    .map(|p| (p.span.clone(), format!("%{{{}%}}", p.data))),
+            )
+            .collect::<Vec<_>>();
+        head.sort_by_key(|(span, _)| span.start);
+        for (span, text) in head {
+            let comments = LineComments::split(self.take_comments(span.end));
+            self.line("", &text, &comments);
+        }
+
+        let rules_start = grammar
+            .rules
+            .first()
+            .map_or(grammar.epilogue.span.start, |r| r.span.start);
+        let comments = LineComments::split(self.take_comments(rules_start));
+        self.line("", "%%", &comments);
+
+        for (i, rule) in grammar.rules.iter().enumerate() {
+            if i > 0 {
+                self.out.push('\n');
+            }
+            self.format_rule(rule);
+        }
+        if !grammar.rules.is_empty() {
+            self.out.push('\n');
+        }
+
+        // The epilogue runs to the end of the input, so all comments left are
+        // on their own lines before it. The epilogue itself is kept verbatim.
+        for comment in self.take_comments(usize::MAX) {
+            self.out.push_str(&comment.text);
+            self.out.push('\n');
+        }
+        self.out.push_str("%%");
+        self.out.push_str(&grammar.epilogue.data);
+        self.out
+    }
+
+    fn format_rule(&mut self, rule: &Rule) {
+        let indent = self.indent();
+        let header_comments = self.take_comments(rule.name.span.end);
+        let mut header = LineComments::split(header_comments);
+
+        let mut previous_end = rule.name.span.end;
+        let mut lines = Vec::new();
+        for (i, alternative) in rule.alternatives.iter().enumerate() {
+            // Comments attached to the alternative's ':' or '|'
+            let mut separator = self.take_comments(alternative.span.start.max(previous_end));
+            let same_line = i == 0 && self.options.first_alternative == FirstAlternative::SameLine;
+            if same_line {
+                // The ':' shares its line with the rule name
+                separator.iter_mut().for_each(|c| c.leading = false);
+            }
+            let body = self.take_comments(alternative.span.end);
+            previous_end = alternative.span.end;
+            let prefix = match (i, self.options.first_alternative) {
+                (0, FirstAlternative::SameLine) => format!("{}: ", rule.name),
+                (0, FirstAlternative::Colon) => format!("{}: ", indent),
+                (0, FirstAlternative::Bar) => format!("{}  ", indent),
+                _ => format!("{}| ", indent),
+            };
+            let mut line = self.alternative_line(&prefix, alternative, separator, body);
+            if same_line {
+                line.comment_indent = String::new();
+            }
+            if i == 0 && self.options.first_alternative == FirstAlternative::Bar && line.is_empty()
+            {
+                continue;
+            }
+            lines.push(line);
+        }
+        let end = LineComments::split(self.take_comments(rule.span.end));
+
+        if self.options.first_alternative == FirstAlternative::Colon {
+            let name = rule.name.to_string();
+            self.line("", &name, &header);
+        } else if self.options.first_alternative == FirstAlternative::Bar {
+            let name = format!("{}:", rule.name);
+            self.line("", &name, &header);
+        } else if let Some(first) = lines.first_mut() {
+            header.before.append(&mut first.comments.before);
+            header.after.append(&mut first.comments.after);
+            header.fix_line_comments();
+            first.comments = header;
+        }
+
+        let column = if self.options.align_actions {
+            lines
+                .iter()
+                .filter(|l| l.action.is_some())
+                .map(|l| width(l.body.last().unwrap()) + 1)
+                .max()
+        } else {
+            None
+        };
+        for line in lines {
+            self.write_alternative(line, column);
+        }
+        self.line(&indent, ";", &end);
+    }
+
+    fn alternative_line(
+        &self,
+        prefix: &str,
+        alternative: &Alternative,
+        separator: Vec<Comment>,
+        body: Vec<Comment>,
+    ) -> AlternativeLine {
+        let action_start = alternative
+            .action
+            .as_ref()
+            .map_or(usize::MAX, |a| a.code.span.start);
+        let mut comments = LineComments::default();
+        // Words of the alternative, sorted by their source position. Block
+        // comments between the symbols stay in place.
+        let mut words = Vec::new();
+        for comment in separator.into_iter().chain(body) {
+            if comment.leading && comment.anchor < alternative.span.start {
+                comments.before.push(comment.text);
+            } else if comment.is_line() || comment.span.start > action_start {
+                comments.after.push(comment.text);
+            } else {
+                words.push((comment.span.start, comment.text));
+            }
+        }
+        comments.fix_line_comments();
+        for element in &alternative.elements {
+            words.push((element.span().start, element.to_string()));
+        }
+        if let Some(precedence) = &alternative.precedence {
+            words.push((precedence.span.start, format!("%prec {}", precedence)));
+        }
+        words.sort_by_key(|(start, _)| *start);
+
+        AlternativeLine {
+            has_words: !words.is_empty(),
+            body: self.wrap(prefix, words.into_iter().map(|(_, w)| w).collect()),
+            continuation: " ".repeat(width(prefix)),
+            action: alternative.action.as_ref().map(|a| a.to_string()),
+            comments,
+            comment_indent: self.indent(),
+        }
+    }
+
+    fn write_alternative(&mut self, line: AlternativeLine, column: Option<usize>) {
+        let AlternativeLine {
+            mut body,
+            has_words,
+            continuation,
+            action,
+            comments,
+            comment_indent,
+        } = line;
+        if let Some(action) = action {
+            let last = body.last_mut().unwrap();
+            let pad = match column {
+                Some(column) if column > width(last) => column - width(last),
+                _ if last.ends_with(' ') => 0,
+                _ => 1,
+            };
+            // Multi-line actions, and actions which do not fit, go on their
+            // own line
+            let too_long = self.options.max_width != 0
+                && width(last) + pad + width(&action) > self.options.max_width;
+            if (too_long || action.contains('\n')) && has_words {
+                body.push(format!("{}{}{}", continuation, self.indent(), action));
+            } else {
+                last.push_str(&" ".repeat(pad));
+                last.push_str(&action);
+            }
+        }
+        for comment in &comments.before {
+            self.out.push_str(&comment_indent);
+            self.out.push_str(comment);
+            self.out.push('\n');
+        }
+        self.line(
+            "",
+            &body.join("\n"),
+            &LineComments {
+                before: Vec::new(),
+                after: comments.after,
+            },
+        );
+    }
+}
+
+// An alternative laid out over one or more lines
+struct AlternativeLine {
+    body: Vec<String>,
+    has_words: bool,
+    // Indentation of continuation lines
+    continuation: String,
+    action: Option<String>,
+    comments: LineComments,
+    comment_indent: String,
+}
+
+impl AlternativeLine {
+    // Whether the line would show nothing but its prefix
+    fn is_empty(&self) -> bool {
+        !self.has_words
+            && self.action.is_none()
+            && self.comments.before.is_empty()
+            && self.comments.after.is_empty()
+    }
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
@@ -1,13 +1,75 @@
+use crate::action::Action;
+use crate::action::Target;
+use crate::escape;
+use crate::token::Spanned;
+use std::ops::Range;
+
 // rule1; rule2; rule3;
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Grammar {
-    pub directives: Vec<Directive>,
+    pub directives: Vec<Spanned<Directive>>,
     pub rules: Vec<Rule>,
-    pub prologues: Vec<String>,
-    pub epilogue: String,
+    // Spans cover the whole `%{ ... %}` block
+    pub prologues: Vec<Spanned<String>>,
+    // Span covers the second `%%` and everything after it
+    pub epilogue: Spanned<String>,
+}
+
+impl Grammar {
+    // The grammar with every mid-rule action moved into a new empty rule,
+    // $@1, $@2, ..., which takes its place, as yacc does. References to
+    // earlier symbols are rewritten to reach below the new rule on the stack,
+    // e.g. $1 in `a b { $1; } c` becomes $-1.
+    pub fn desugar_mid_rule_actions(&self) -> Grammar {
+        let mut grammar = self.clone();
+        let mut extra = Vec::new();
+        for rule in &mut grammar.rules {
+            let original = rule.clone();
+            for (alternative, before) in rule.alternatives.iter_mut().zip(&original.alternatives) {
+                for (i, element) in alternative.elements.iter_mut().enumerate() {
+                    let Element::Action(action) = element else {
+                        continue;
+                    };
+                    let code = action.rewrite(|reference| {
+                        let n = match &reference.target {
+                            Target::Index(n) => *n,
+                            Target::Name(name) | Target::BracketedName(name) => {
+                                match original.named_references(before, i, name)[..] {
+                                    [(Some(n), _)] => n,
+                                    _ => return None,
+                                }
+                            }
+                            Target::Result | Target::OutOfRange(_) => return None,
+                        };
+                        let mut reference = reference.clone();
+                        reference.target = Target::Index(n.checked_sub(i as i32)?);
+                        Some(reference.to_string())
+                    });
+                    let span = action.code.span.clone();
+                    let name = Spanned::new(format!("$@{}", extra.len() + 1), span.clone());
+                    extra.push(Rule {
+                        name: name.clone(),
+                        alternatives: vec![Alternative {
+                            elements: Vec::new(),
+                            precedence: None,
+                            action: Some(Action::new(Spanned::new(code, span.clone()))),
+                            span: span.clone(),
+                        }],
+                        span,
+                    });
+                    *element = Element::Symbol(name);
+                }
+            }
+        }
+        grammar.rules.extend(extra);
+        grammar
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind"))]
 pub enum Directive {
     // %pure-parser
     // ------------
@@ -25,6 +87,13 @@ pub enum Directive {
     Expect {
         number: i32,
     },
+    // %expect-rr number
+    // ------------
+    // The expected number of reduce/reduce conflicts. Without it, %expect
+    // expects none.
+    ExpectRr {
+        number: i32,
+    },
     // %name-prefix="prefix"
     // ------------
     // Rename the external symbols used in the parser so  that
@@ -40,7 +109,8 @@ pub enum Directive {
     Locations,
     // %parse-param { params }
     // ------------
-    // Adds a parameter to the yyparse function signature.
+    // Adds a parameter to the yyparse function signature. The params are
+    // the text between the braces.
     ParseParam {
         params: String,
     },
@@ -50,43 +120,160 @@ pub enum Directive {
         params: String,
     },
     // %union { code }
+    // The code between the braces, kept verbatim
     Union {
         code: String,
     },
     // %type <type> identifiers
     Type {
-        type_name: String,
-        rule_names: Vec<String>,
+        type_name: Spanned<String>,
+        rule_names: Vec<Spanned<String>>,
     },
-    // %token [<token>] identifiers
+    // %token [<token>] identifier ["alias"] ...
     Token {
-        token_name: Option<String>,
-        rule_names: Vec<String>,
+        token_name: Option<Spanned<String>>,
+        rule_names: Vec<TokenDeclaration>,
     },
     // %left identifiers
     Left {
-        rule_names: Vec<String>,
+        rule_names: Vec<Spanned<String>>,
     },
     // %right identifiers
     Right {
-        rule_names: Vec<String>,
+        rule_names: Vec<Spanned<String>>,
     },
     // %nonassoc identifiers
     NonAssoc {
-        rule_names: Vec<String>,
+        rule_names: Vec<Spanned<String>>,
     },
 }
 
+impl Directive {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Directive::PureParser => "%pure-parser",
+            Directive::Expect { .. } => "%expect",
+            Directive::ExpectRr { .. } => "%expect-rr",
+            Directive::NamePrefix { .. } => "%name-prefix",
+            Directive::Locations => "%locations",
+            Directive::ParseParam { .. } => "%parse-param",
+            Directive::LexProgram { .. } => "%lex-param",
+            Directive::Union { .. } => "%union",
+            Directive::Type { .. } => "%type",
+            Directive::Token { .. } => "%token",
+            Directive::Left { .. } => "%left",
+            Directive::Right { .. } => "%right",
+            Directive::NonAssoc { .. } => "%nonassoc",
+        }
+    }
+}
+
+impl Spanned<Directive> {
+    // The span of the `%keyword` which starts the directive
+    pub fn keyword_span(&self) -> Range<usize> {
+        self.span.start..self.span.start + self.data.keyword().len()
+    }
+}
+
+// PLUS "+" in a %token directive
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenDeclaration {
+    pub name: Spanned<String>,
+    // The string literal as written, e.g. "+", which can be used instead of
+    // the name in rules
+    pub alias: Option<Spanned<String>>,
+}
+
+impl TokenDeclaration {
+    // The string denoted by the alias
+    pub fn alias_value(&self) -> Option<String> {
+        self.alias
+            .as_ref()
+            .and_then(|alias| escape::string_value(&alias.data))
+    }
+}
+
 // foo: bar baz { ... } | qux { ...};
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rule {
-    pub name: String,
+    pub name: Spanned<String>,
     pub alternatives: Vec<Alternative>,
+    // From the rule name up to and including the ';'
+    pub span: Range<usize>,
+}
+
+impl Rule {
+    // What $name means in an action of the alternative which sees its first
+    // `visible` elements: None for the rule's own value, Some(n) for $n, with
+    // the span of the matching name. Several matches make it ambiguous.
+    pub fn named_references(
+        &self,
+        alternative: &Alternative,
+        visible: usize,
+        name: &str,
+    ) -> Vec<(Option<i32>, Range<usize>)> {
+        let mut matches = Vec::new();
+        if self.name.data == name {
+            matches.push((None, self.name.span.clone()));
+        }
+        for (i, element) in alternative.elements[..visible].iter().enumerate() {
+            if let Element::Symbol(symbol) = element {
+                if symbol.data == name {
+                    matches.push((Some(i as i32 + 1), symbol.span.clone()));
+                }
+            }
+        }
+        matches
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Alternative {
-    pub elements: Vec<String>,
-    pub precedence: Option<String>,
-    pub action: Option<String>,
+    pub elements: Vec<Element>,
+    pub precedence: Option<Spanned<String>>,
+    pub action: Option<Action>,
+    // Excludes the surrounding ':', '|' and ';', empty for an empty alternative
+    pub span: Range<usize>,
+}
+
+// bar, 'c', "+", { mid(); }
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind"))]
+pub enum Element {
+    // A token or nonterminal name
+    Symbol(Spanned<String>),
+    // A character literal as written, e.g. '\n', and the character it denotes
+    Char {
+        text: Spanned<String>,
+        value: char,
+    },
+    // A string alias of a token as written, e.g. "+", and the string it
+    // denotes
+    String {
+        text: Spanned<String>,
+        value: String,
+    },
+    // An action followed by more symbols. It takes up a position like a
+    // symbol, so $n in later actions counts it.
+    Action(Action),
+}
+
+impl Element {
+    // The element as written in the grammar
+    pub fn text(&self) -> &Spanned<String> {
+        match self {
+            Element::Symbol(name) => name,
+            Element::Char { text, .. } => text,
+            Element::String { text, .. } => text,
+            Element::Action(action) => &action.code,
+        }
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.text().span.clone()
+    }
 }
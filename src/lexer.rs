@@ -8,6 +8,8 @@ pub struct Lexer<'a> {
     input: &'a str,
     chars: Peekable<CharIndices<'a>>,
     percent_percent_count: usize,
+    // Whether whitespace and comments are returned as tokens
+    trivia: bool,
 }
 
 impl<'a> Iterator for Lexer<'a> {
@@ -15,12 +17,18 @@ impl<'a> Iterator for Lexer<'a> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let mut start = self.curr_pos();
-        let token = 'outer: loop {
+        let token = loop {
             match self.chars.next()?.1 {
                 // '<char>'
                 '\'' => {
-                    if self.chars.next().is_none() {
-                        break Token::Err;
+                    match self.chars.next() {
+                        Some((_, '\\')) => {
+                            if !self.escape() {
+                                break Token::Err;
+                            }
+                        }
+                        Some(_) => {}
+                        None => break Token::Err,
                     }
                     if let Some((_, '\'')) = self.chars.next() {
                         break Token::Char;
@@ -29,35 +37,34 @@ impl<'a> Iterator for Lexer<'a> {
                 }
                 '/' => match self.chars.next() {
                     Some((_, '/')) => {
-                        for (_, c) in self.chars.by_ref() {
-                            if c == '\n' {
+                        while let Some((_, c)) = self.chars.peek() {
+                            if *c == '\n' {
                                 break;
                             }
+                            self.chars.next();
+                        }
+                        if self.trivia {
+                            break Token::Comment;
                         }
                     }
                     Some((_, '*')) => {
-                        loop {
-                            match self.chars.next() {
-                                Some((_, '*')) => match self.chars.peek() {
-                                    Some((_, '/')) => {
-                                        self.chars.next();
-                                        break;
-                                    }
-                                    Some(_) => {}
-                                    None => {
-                                        break 'outer Token::Err;
-                                    }
-                                },
-                                Some(_) => {}
-                                None => {
-                                    break 'outer Token::Err;
-                                }
-                            }
+                        if !self.block_comment() {
+                            break Token::Err;
+                        }
+                        if self.trivia {
+                            break Token::Comment;
                         }
                     }
                     _ => break Token::Err,
                 },
-                '\n' | ' ' | '\t' => {}
+                '\n' | ' ' | '\t' => {
+                    if self.trivia {
+                        while let Some((_, '\n' | ' ' | '\t')) = self.chars.peek() {
+                            self.chars.next();
+                        }
+                        break Token::Whitespace;
+                    }
+                }
                 '=' => {
                     break Token::Equal;
                 }
@@ -73,6 +80,11 @@ impl<'a> Iterator for Lexer<'a> {
                             Some((_, '"')) => {
                                 break Token::String;
                             }
+                            Some((_, '\\')) => {
+                                if !self.escape() {
+                                    break Token::Err;
+                                }
+                            }
                             Some(_) => {}
                             None => {
                                 break Token::Err;
@@ -102,6 +114,11 @@ impl<'a> Iterator for Lexer<'a> {
                                         break Token::Err;
                                     }
                                 },
+                                Some((_, c @ ('"' | '\'' | '/'))) => {
+                                    if !self.skip_c_token(c) {
+                                        break Token::Err;
+                                    }
+                                }
                                 Some(_) => {}
                                 None => {
                                     break Token::Err;
@@ -142,6 +159,11 @@ impl<'a> Iterator for Lexer<'a> {
                                     break Token::Code;
                                 }
                             }
+                            Some((_, c @ ('"' | '\'' | '/'))) => {
+                                if !self.skip_c_token(c) {
+                                    break Token::Err;
+                                }
+                            }
                             Some(_) => {}
                             None => {
                                 break Token::Err;
@@ -150,7 +172,8 @@ impl<'a> Iterator for Lexer<'a> {
                     };
                 }
                 'a'..='z' | 'A'..='Z' => {
-                    while let Some((_, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.' | '-')) = self.chars.peek()
+                    while let Some((_, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '.' | '-')) =
+                        self.chars.peek()
                     {
                         self.chars.next();
                     }
@@ -181,7 +204,113 @@ impl<'a> Lexer<'a> {
             input,
             chars: input.char_indices().peekable(),
             percent_percent_count: 0,
+            trivia: false,
+        }
+    }
+
+    // A lexer which also returns Whitespace and Comment tokens, so that the
+    // tokens cover every byte of the input.
+    pub fn with_trivia(input: &'a str) -> Self {
+        Lexer {
+            trivia: true,
+            ..Lexer::new(input)
+        }
+    }
+
+    // Consumes the rest of a C escape sequence after the backslash: a simple
+    // escape like \n, up to three octal digits or \x and hex digits.
+    fn escape(&mut self) -> bool {
+        match self.chars.next() {
+            Some((_, 'n' | 't' | 'r' | 'a' | 'b' | 'f' | 'v' | 'e' | '\\' | '\'' | '"' | '?')) => {
+                true
+            }
+            Some((_, '0'..='7')) => {
+                for _ in 0..2 {
+                    if let Some((_, '0'..='7')) = self.chars.peek() {
+                        self.chars.next();
+                    }
+                }
+                true
+            }
+            Some((_, 'x')) => {
+                let mut digits = 0;
+                while let Some((_, '0'..='9' | 'a'..='f' | 'A'..='F')) = self.chars.peek() {
+                    self.chars.next();
+                    digits += 1;
+                }
+                digits > 0
+            }
+            _ => false,
+        }
+    }
+
+    // Consumes the rest of a /* */ comment after the opening "/*"
+    fn block_comment(&mut self) -> bool {
+        loop {
+            match self.chars.next() {
+                Some((_, '*')) => {
+                    if let Some((_, '/')) = self.chars.peek() {
+                        self.chars.next();
+                        return true;
+                    }
+                }
+                Some(_) => {}
+                None => return false,
+            }
+        }
+    }
+
+    // Inside C code (actions and prologues), consumes the rest of a string,
+    // character literal or comment started by the character just read, so
+    // braces and %} inside them are ignored. Returns false if it is not
+    // terminated.
+    fn skip_c_token(&mut self, c: char) -> bool {
+        match c {
+            // The rest of the lifetime is plain code
+            '\'' if self.at_lifetime() => true,
+            '"' | '\'' => loop {
+                match self.chars.next() {
+                    Some((_, '\\')) => {
+                        self.chars.next();
+                    }
+                    Some((_, q)) if q == c => return true,
+                    // Literals do not span lines, so this was a stray quote
+                    Some((_, '\n')) => return true,
+                    Some(_) => {}
+                    None => return false,
+                }
+            },
+            '/' => match self.chars.peek() {
+                Some((_, '*')) => {
+                    self.chars.next();
+                    self.block_comment()
+                }
+                Some((_, '/')) => {
+                    while let Some((_, c)) = self.chars.peek() {
+                        if *c == '\n' {
+                            break;
+                        }
+                        self.chars.next();
+                    }
+                    true
+                }
+                _ => true,
+            },
+            _ => true,
+        }
+    }
+
+    // Whether the quote just consumed starts a Rust lifetime like 'a or
+    // 'static rather than a character literal: a name with no closing quote
+    fn at_lifetime(&self) -> bool {
+        let mut chars = self.chars.clone();
+        if !matches!(chars.next(), Some((_, 'a'..='z' | 'A'..='Z' | '_'))) {
+            return false;
+        }
+        while let Some((_, 'a'..='z' | 'A'..='Z' | '0'..='9' | '_')) = chars.peek() {
+            chars.next();
         }
+        !matches!(chars.peek(), Some((_, '\'')))
     }
 
     fn curr_pos(&mut self) -> usize {
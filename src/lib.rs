@@ -0,0 +1,20 @@
+pub mod action;
+pub mod analysis;
+pub mod bison;
+pub mod check;
+pub mod codegen;
+pub mod counterexample;
+pub mod cst;
+pub mod diagnostics;
+pub mod display;
+pub mod error;
+pub mod escape;
+pub mod export;
+pub mod format;
+pub mod grammar;
+pub mod lexer;
+pub mod lr;
+pub mod parser;
+pub mod symbols;
+pub mod tables;
+pub mod token;
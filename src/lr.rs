@@ -0,0 +1,530 @@
+// LR automata of a grammar, built over the productions augmented with
+// $accept: start $end. LALR(1) automata are the LR(0) states, with the
+// lookaheads of their reductions computed by the method of DeRemer and
+// Pennello. Canonical LR(1) automata keep apart the states which differ in
+// their lookaheads, and minimal LR(1) automata merge those of them which
+// Pager's weak compatibility says cannot conflict. Mid-rule actions are not
+// symbols, so grammars should have them desugared into empty rules first.
+use crate::analysis::productions;
+use crate::analysis::Analysis;
+use crate::analysis::Production;
+use crate::grammar::Grammar;
+use crate::symbols::Precedence;
+use crate::symbols::SymbolId;
+use crate::symbols::SymbolTable;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Lalr,
+    Canonical,
+    Minimal,
+}
+
+// A production with a dot before the symbol at `dot`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Item {
+    pub production: usize,
+    pub dot: usize,
+}
+
+impl Item {
+    fn advance(self) -> Item {
+        Item {
+            production: self.production,
+            dot: self.dot + 1,
+        }
+    }
+}
+
+// A completed item and the tokens on which to reduce by it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reduction {
+    pub production: usize,
+    pub lookahead: BTreeSet<SymbolId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct State {
+    // The kernel items, sorted
+    pub items: Vec<Item>,
+    pub transitions: BTreeMap<SymbolId, usize>,
+    // The completed items, including the empty productions of the closure,
+    // in the order of the productions
+    pub reductions: Vec<Reduction>,
+}
+
+impl State {
+    fn new(items: Vec<Item>) -> Self {
+        State {
+            items,
+            transitions: BTreeMap::new(),
+            reductions: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Automaton {
+    // Production 0 is $accept: start $end, whose rule and alternative are
+    // meaningless. The others are those of the grammar, in order.
+    pub productions: Vec<Production>,
+    // The precedence of each production: that of its %prec token, or else
+    // of its last token
+    pub precedence: Vec<Option<Precedence>>,
+    pub states: Vec<State>,
+    // The number of tokens, which come first in the symbol table
+    tokens: usize,
+    accept: SymbolId,
+    by_lhs: HashMap<SymbolId, Vec<usize>>,
+}
+
+// LR(1) items sharing a production and dot are kept as one, with the union
+// of their lookaheads
+type Kernel = Vec<(Item, BTreeSet<SymbolId>)>;
+
+impl Automaton {
+    pub fn build(grammar: &Grammar, symbols: &SymbolTable, mode: Mode) -> Self {
+        match mode {
+            Mode::Lalr => Automaton::lalr(grammar, symbols),
+            Mode::Canonical | Mode::Minimal => Automaton::lr1(grammar, symbols, mode),
+        }
+    }
+
+    // The LALR(1) automaton: the LR(0) states with lookaheads
+    pub fn lalr(grammar: &Grammar, symbols: &SymbolTable) -> Self {
+        let mut automaton = Automaton::lr0(grammar, symbols);
+        automaton.compute_lookaheads(&Analysis::new(grammar, symbols));
+        automaton
+    }
+
+    // The LR(0) states, with empty lookaheads
+    pub fn lr0(grammar: &Grammar, symbols: &SymbolTable) -> Self {
+        let mut automaton = Automaton::augmented(grammar, symbols);
+        let mut ids = HashMap::new();
+        let initial = vec![Item {
+            production: 0,
+            dot: 0,
+        }];
+        ids.insert(initial.clone(), 0);
+        automaton.states.push(State::new(initial));
+        let mut i = 0;
+        while i < automaton.states.len() {
+            let closure = automaton.closure(&automaton.states[i].items);
+            let mut kernels = BTreeMap::<SymbolId, Vec<Item>>::new();
+            let mut reductions = Vec::new();
+            for item in closure {
+                match automaton.next_symbol(item) {
+                    // Accepting takes the place of shifting $end
+                    Some(SymbolTable::END) if item.production == 0 => {}
+                    Some(symbol) => kernels.entry(symbol).or_default().push(item.advance()),
+                    None => reductions.push(Reduction {
+                        production: item.production,
+                        lookahead: BTreeSet::new(),
+                    }),
+                }
+            }
+            for (symbol, mut kernel) in kernels {
+                kernel.sort();
+                let next = match ids.get(&kernel) {
+                    Some(&next) => next,
+                    None => {
+                        ids.insert(kernel.clone(), automaton.states.len());
+                        automaton.states.push(State::new(kernel));
+                        automaton.states.len() - 1
+                    }
+                };
+                automaton.states[i].transitions.insert(symbol, next);
+            }
+            reductions.sort_by_key(|r| r.production);
+            automaton.states[i].reductions = reductions;
+            i += 1;
+        }
+        automaton
+    }
+
+    // The canonical or minimal LR(1) automaton. A state whose lookaheads grow
+    // by merging is visited again to pass them on to its successors.
+    fn lr1(grammar: &Grammar, symbols: &SymbolTable, mode: Mode) -> Self {
+        let analysis = Analysis::new(grammar, symbols);
+        let mut automaton = Automaton::augmented(grammar, symbols);
+        let initial = Item {
+            production: 0,
+            dot: 0,
+        };
+        let mut kernels = vec![vec![(initial, BTreeSet::new())]];
+        let mut by_core = HashMap::from([(vec![initial], vec![0])]);
+        automaton.states.push(State::new(vec![initial]));
+        let mut pending = VecDeque::from([0]);
+        let mut queued = vec![true];
+        while let Some(i) = pending.pop_front() {
+            queued[i] = false;
+            let mut successors = BTreeMap::<SymbolId, Kernel>::new();
+            let mut reductions = Vec::new();
+            for (item, lookahead) in automaton.lr1_closure(&kernels[i], &analysis) {
+                match automaton.next_symbol(item) {
+                    Some(SymbolTable::END) if item.production == 0 => {}
+                    Some(symbol) => successors
+                        .entry(symbol)
+                        .or_default()
+                        .push((item.advance(), lookahead)),
+                    None => reductions.push(Reduction {
+                        production: item.production,
+                        lookahead,
+                    }),
+                }
+            }
+            let mut transitions = BTreeMap::new();
+            for (symbol, mut kernel) in successors {
+                kernel.sort();
+                let core = kernel.iter().map(|(item, _)| *item).collect::<Vec<_>>();
+                let states = by_core.entry(core.clone()).or_default();
+                let target = states
+                    .iter()
+                    .copied()
+                    .find(|&j| compatible(mode, &kernels[j], &kernel));
+                let next = match target {
+                    Some(j) => {
+                        let mut grown = false;
+                        for ((_, old), (_, new)) in kernels[j].iter_mut().zip(&kernel) {
+                            let len = old.len();
+                            old.extend(new.iter().copied());
+                            grown |= old.len() != len;
+                        }
+                        if grown && !queued[j] {
+                            queued[j] = true;
+                            pending.push_back(j);
+                        }
+                        j
+                    }
+                    None => {
+                        let j = kernels.len();
+                        states.push(j);
+                        kernels.push(kernel);
+                        automaton.states.push(State::new(core));
+                        queued.push(true);
+                        pending.push_back(j);
+                        j
+                    }
+                };
+                transitions.insert(symbol, next);
+            }
+            reductions.sort_by_key(|r| r.production);
+            automaton.states[i].transitions = transitions;
+            automaton.states[i].reductions = reductions;
+        }
+        automaton.remove_unreachable();
+        automaton
+    }
+
+    // The closure of an LR(1) kernel. The items of a nonterminal after a dot
+    // have as lookahead what can follow it, which is its own lookahead when
+    // the rest of the production is nullable.
+    fn lr1_closure(&self, kernel: &Kernel, analysis: &Analysis) -> Kernel {
+        let mut items = kernel.clone();
+        let mut index = HashMap::<Item, usize>::new();
+        let mut pending = (0..items.len()).collect::<Vec<_>>();
+        while let Some(i) = pending.pop() {
+            let (item, lookahead) = items[i].clone();
+            let Some(symbol) = self.next_symbol(item) else {
+                continue;
+            };
+            let rest = &self.productions[item.production].rhs[item.dot + 1..];
+            let (mut follow, nullable) = analysis.first_of(rest);
+            if nullable {
+                follow.extend(lookahead.iter().copied());
+            }
+            for &production in self.productions_of(symbol) {
+                let item = Item { production, dot: 0 };
+                match index.get(&item) {
+                    Some(&j) => {
+                        let existing = &mut items[j].1;
+                        let len = existing.len();
+                        existing.extend(follow.iter().copied());
+                        if existing.len() != len && !pending.contains(&j) {
+                            pending.push(j);
+                        }
+                    }
+                    None => {
+                        index.insert(item, items.len());
+                        pending.push(items.len());
+                        items.push((item, follow.clone()));
+                    }
+                }
+            }
+        }
+        items
+    }
+
+    // Drops the states which merging left without a transition to them,
+    // numbering the others in the order they are reached
+    fn remove_unreachable(&mut self) {
+        let mut numbers = vec![None; self.states.len()];
+        let mut order = vec![0];
+        numbers[0] = Some(0);
+        let mut i = 0;
+        while i < order.len() {
+            for &next in self.states[order[i]].transitions.values() {
+                if numbers[next].is_none() {
+                    numbers[next] = Some(order.len());
+                    order.push(next);
+                }
+            }
+            i += 1;
+        }
+        let mut states = std::mem::take(&mut self.states)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        for old in order {
+            let mut state = states[old].take().unwrap();
+            for next in state.transitions.values_mut() {
+                *next = numbers[*next].unwrap();
+            }
+            self.states.push(state);
+        }
+    }
+
+    // The augmented productions, without any states
+    fn augmented(grammar: &Grammar, symbols: &SymbolTable) -> Self {
+        let accept = SymbolId(symbols.len());
+        let mut productions = productions(grammar, symbols);
+        productions.insert(
+            0,
+            Production {
+                lhs: accept,
+                rhs: symbols
+                    .start()
+                    .into_iter()
+                    .chain([SymbolTable::END])
+                    .collect(),
+                rule: 0,
+                alternative: 0,
+            },
+        );
+        let precedence = productions
+            .iter()
+            .enumerate()
+            .map(|(i, production)| {
+                if i == 0 {
+                    return None;
+                }
+                let alternative =
+                    &grammar.rules[production.rule].alternatives[production.alternative];
+                let token = match &alternative.precedence {
+                    Some(name) => symbols.get(&name.data),
+                    None => production
+                        .rhs
+                        .iter()
+                        .rev()
+                        .find(|&&s| symbols.symbol(s).is_terminal())
+                        .copied(),
+                };
+                token.and_then(|id| symbols.symbol(id).precedence)
+            })
+            .collect();
+        let mut by_lhs = HashMap::<SymbolId, Vec<usize>>::new();
+        for (i, production) in productions.iter().enumerate() {
+            by_lhs.entry(production.lhs).or_default().push(i);
+        }
+        Automaton {
+            productions,
+            precedence,
+            states: Vec::new(),
+            tokens: symbols.terminals().count(),
+            accept,
+            by_lhs,
+        }
+    }
+
+    // The left-hand side of production 0, which is not in the symbol table
+    pub fn accept(&self) -> SymbolId {
+        self.accept
+    }
+
+    pub fn is_terminal(&self, id: SymbolId) -> bool {
+        id.0 < self.tokens
+    }
+
+    // The state in which $end is accepted, reached from the initial state
+    // by the start symbol
+    pub fn final_state(&self) -> usize {
+        match self.productions[0].rhs[..] {
+            [start, _] => self.states[0].transitions[&start],
+            _ => 0,
+        }
+    }
+
+    // The productions of a nonterminal
+    pub fn productions_of(&self, lhs: SymbolId) -> &[usize] {
+        self.by_lhs.get(&lhs).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn next_symbol(&self, item: Item) -> Option<SymbolId> {
+        self.productions[item.production].rhs.get(item.dot).copied()
+    }
+
+    // The kernel items followed by the items of the productions of every
+    // nonterminal after a dot
+    pub fn closure(&self, kernel: &[Item]) -> Vec<Item> {
+        let mut items = kernel.to_vec();
+        let mut added = HashSet::new();
+        let mut i = 0;
+        while i < items.len() {
+            if let Some(symbol) = self.next_symbol(items[i]) {
+                for &production in self.productions_of(symbol) {
+                    if added.insert(production) {
+                        items.push(Item { production, dot: 0 });
+                    }
+                }
+            }
+            i += 1;
+        }
+        items
+    }
+
+    // DeRemer and Pennello: the lookahead of a reduction is the union of the
+    // FOLLOW sets of the nonterminal transitions it looks back to. Those are
+    // what the target states read directly, or through nullable
+    // nonterminals, plus the FOLLOW sets of the transitions they include.
+    fn compute_lookaheads(&mut self, analysis: &Analysis) {
+        let mut transitions = Vec::new();
+        let mut numbers = HashMap::new();
+        for (p, state) in self.states.iter().enumerate() {
+            for &symbol in state.transitions.keys() {
+                if !self.is_terminal(symbol) {
+                    numbers.insert((p, symbol), transitions.len());
+                    transitions.push((p, symbol));
+                }
+            }
+        }
+
+        let final_state = self.final_state();
+        let mut direct_reads = Vec::new();
+        let mut reads = Vec::new();
+        for &(p, symbol) in &transitions {
+            let r = self.states[p].transitions[&symbol];
+            let mut direct = BTreeSet::new();
+            let mut edges = Vec::new();
+            for &next in self.states[r].transitions.keys() {
+                if self.is_terminal(next) {
+                    direct.insert(next);
+                } else if analysis.is_nullable(next) {
+                    edges.push(numbers[&(r, next)]);
+                }
+            }
+            if r == final_state {
+                direct.insert(SymbolTable::END);
+            }
+            direct_reads.push(direct);
+            reads.push(edges);
+        }
+        let read = digraph(&reads, direct_reads);
+
+        let mut includes = vec![Vec::new(); transitions.len()];
+        let mut lookback = HashMap::<(usize, usize), Vec<usize>>::new();
+        for (t, &(p, lhs)) in transitions.iter().enumerate() {
+            for &production in self.productions_of(lhs) {
+                let rhs = &self.productions[production].rhs;
+                let mut q = p;
+                for (i, &symbol) in rhs.iter().enumerate() {
+                    if !self.is_terminal(symbol)
+                        && rhs[i + 1..].iter().all(|&s| analysis.is_nullable(s))
+                    {
+                        includes[numbers[&(q, symbol)]].push(t);
+                    }
+                    q = self.states[q].transitions[&symbol];
+                }
+                lookback.entry((q, production)).or_default().push(t);
+            }
+        }
+        let follow = digraph(&includes, read);
+
+        for (q, state) in self.states.iter_mut().enumerate() {
+            for reduction in &mut state.reductions {
+                for &t in lookback
+                    .get(&(q, reduction.production))
+                    .into_iter()
+                    .flatten()
+                {
+                    reduction.lookahead.extend(follow[t].iter().copied());
+                }
+            }
+        }
+    }
+}
+
+// The smallest sets containing their base set and the sets of every node
+// they have an edge to. Nodes on a cycle end up with the same set.
+fn digraph(edges: &[Vec<usize>], base: Vec<BTreeSet<SymbolId>>) -> Vec<BTreeSet<SymbolId>> {
+    let mut digraph = Digraph {
+        edges,
+        sets: base,
+        depth: vec![0; edges.len()],
+        stack: Vec::new(),
+    };
+    for x in 0..edges.len() {
+        if digraph.depth[x] == 0 {
+            digraph.traverse(x);
+        }
+    }
+    digraph.sets
+}
+
+struct Digraph<'a> {
+    edges: &'a [Vec<usize>],
+    sets: Vec<BTreeSet<SymbolId>>,
+    // 0 for unvisited nodes, usize::MAX for finished ones
+    depth: Vec<usize>,
+    stack: Vec<usize>,
+}
+
+impl Digraph<'_> {
+    fn traverse(&mut self, x: usize) {
+        self.stack.push(x);
+        let depth = self.stack.len();
+        self.depth[x] = depth;
+        for &y in self.edges[x].iter() {
+            if self.depth[y] == 0 {
+                self.traverse(y);
+            }
+            self.depth[x] = self.depth[x].min(self.depth[y]);
+            let set = self.sets[y].clone();
+            self.sets[x].extend(set);
+        }
+        if self.depth[x] == depth {
+            while let Some(top) = self.stack.pop() {
+                self.depth[top] = usize::MAX;
+                if top == x {
+                    break;
+                }
+                self.sets[top] = self.sets[x].clone();
+            }
+        }
+    }
+}
+
+// Whether two LR(1) kernels with the same items can be one state. Merging
+// canonical states needs the same lookaheads. Pager's weak compatibility
+// allows it unless a token of one item in the one state and of another item
+// in the other state could make a reduce/reduce conflict which neither state
+// had.
+fn compatible(mode: Mode, a: &Kernel, b: &Kernel) -> bool {
+    if mode == Mode::Canonical {
+        return a == b;
+    }
+    for i in 0..a.len() {
+        for j in i + 1..a.len() {
+            let crossed = !a[i].1.is_disjoint(&b[j].1) || !a[j].1.is_disjoint(&b[i].1);
+            if crossed && a[i].1.is_disjoint(&a[j].1) && b[i].1.is_disjoint(&b[j].1) {
+                return false;
+            }
+        }
+    }
+    true
+}
@@ -1,49 +1,272 @@
-pub mod display;
-pub mod grammar;
-pub mod lexer;
-pub mod parser;
-pub mod token;
+use std::io::IsTerminal;
+use yacc_parser::bison;
+use yacc_parser::check;
+use yacc_parser::codegen;
+use yacc_parser::diagnostics::Diagnostic;
+use yacc_parser::diagnostics::Renderer;
+use yacc_parser::diagnostics::Severity;
+use yacc_parser::export;
+use yacc_parser::export::Dialect;
+use yacc_parser::format::FirstAlternative;
+use yacc_parser::format::FormatOptions;
+use yacc_parser::grammar::Grammar;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::lr::Automaton;
+use yacc_parser::lr::Mode;
+use yacc_parser::parser;
+use yacc_parser::symbols::SymbolTable;
+use yacc_parser::tables;
+use yacc_parser::tables::Tables;
+use yacc_parser::token;
 
-use lexer::Lexer;
+const USAGE: &str = "usage: yacc-parser [--mode lalr|canonical|minimal] <file>
+       yacc-parser generate rust|c|header [-o <output>] <file>
+       yacc-parser export --format json|ebnf|iso-ebnf [-o <output>] <file>
+       yacc-parser fmt [--check] [--indent N] [--align-actions]
+                       [--first-alternative colon|same-line|bar] [--max-width N]
+                       [--strip-comments] <file>...";
 
 fn main() {
-    let input_file = std::env::args().nth(1).expect("No input file");
-    let input = std::fs::read_to_string(input_file).expect("Failed to read input file");
-
-    // Testing lexer
-    let lexer = Lexer::new(&input);
-    for t in lexer {
-        if t.data == token::Token::Err {
-            let (line0, col0) = line_col(&input, t.span.start);
-            let (line1, col1) = line_col(&input, t.span.end);
-            panic!(
-                r#"Un-scannable token at {}:{}..{}:{}: "{}""#,
-                line0,
-                col0,
-                line1,
-                col1,
-                &input[t.span.clone()]
-            );
-        }
-        println!("{:?}", t);
-    }
-
-    let lexer = Lexer::new(&input);
-    let mut parser = parser::Parser::new(&input, lexer);
-    let _grammar = parser.parse_grammar();
-    // println!("{}", grammar);
-}
-
-fn line_col(input: &str, pos: usize) -> (usize, usize) {
-    let mut line = 1;
-    let mut col = 1;
-    for c in input.chars().take(pos) {
-        if c == '\n' {
-            line += 1;
-            col = 1;
-        } else {
-            col += 1;
-        }
-    }
-    (line, col)
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let code = match args.first().map(String::as_str) {
+        Some("fmt") => fmt(&args[1..]),
+        Some("generate") => generate(&args[1..]),
+        Some("export") => export(&args[1..]),
+        Some("--mode") => {
+            let mode = match args.get(1).map(String::as_str) {
+                Some("lalr") => Mode::Lalr,
+                Some("canonical") => Mode::Canonical,
+                Some("minimal") => Mode::Minimal,
+                _ => std::process::exit(usage()),
+            };
+            match &args[2..] {
+                [input_file] => check(input_file, mode),
+                _ => usage(),
+            }
+        }
+        Some(input_file) => check(input_file, Mode::Lalr),
+        None => usage(),
+    };
+    std::process::exit(code);
+}
+
+fn usage() -> i32 {
+    eprintln!("{}", USAGE);
+    2
+}
+
+fn read(input_file: &str) -> String {
+    std::fs::read_to_string(input_file).expect("Failed to read input file")
+}
+
+fn report(input_file: &str, input: &str, diagnostics: &[Diagnostic]) {
+    let renderer = Renderer::new(input_file, input).with_color(std::io::stderr().is_terminal());
+    for diagnostic in diagnostics {
+        eprintln!("{}", renderer.render(diagnostic));
+    }
+}
+
+fn check(input_file: &str, mode: Mode) -> i32 {
+    let input = read(input_file);
+
+    let mut diagnostics = Lexer::new(&input)
+        .filter(|t| t.data == token::Token::Err)
+        .map(|t| {
+            Diagnostic::error("un-scannable token".to_string(), t.span)
+                .with_primary_label("not valid here".to_string())
+        })
+        .collect::<Vec<_>>();
+
+    if diagnostics.is_empty() {
+        let lexer = Lexer::new(&input);
+        let mut parser = parser::Parser::new(&input, lexer);
+        let (grammar, parse_diagnostics) = parser.parse_grammar_recovering();
+        diagnostics = parse_diagnostics;
+        if diagnostics.is_empty() {
+            diagnostics = check::check_symbols(&grammar);
+            diagnostics.extend(check::check_actions(&grammar));
+        }
+        if !diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            diagnostics.extend(conflicts(&grammar, mode));
+        }
+    }
+
+    report(input_file, &input, &diagnostics);
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        1
+    } else {
+        0
+    }
+}
+
+// Builds the parse tables, reporting their conflicts
+fn conflicts(grammar: &Grammar, mode: Mode) -> Vec<Diagnostic> {
+    let grammar = grammar.desugar_mid_rule_actions();
+    let (symbols, _) = SymbolTable::new(&grammar);
+    let automaton = Automaton::build(&grammar, &symbols, mode);
+    let tables = Tables::new(&automaton, &symbols);
+    tables::check_conflicts(&grammar, &symbols, &automaton, &tables)
+}
+
+// Generates a parser from the grammar, written to the output file or else
+// to stdout. Grammars with errors generate nothing.
+fn generate(args: &[String]) -> i32 {
+    let Some((language, args)) = args.split_first() else {
+        return usage();
+    };
+    let (output, input_file) = match args {
+        [flag, output, input_file] if flag == "-o" => (Some(output), input_file),
+        [input_file] if !input_file.starts_with('-') => (None, input_file),
+        _ => return usage(),
+    };
+    let input = read(input_file);
+    let mut parser = parser::Parser::new(&input, Lexer::new(&input));
+    let (grammar, mut diagnostics) = parser.parse_grammar_recovering();
+    if diagnostics.is_empty() {
+        diagnostics = check::check_symbols(&grammar);
+        diagnostics.extend(check::check_actions(&grammar));
+    }
+    if language == "rust" {
+        // A Rust action may give the value of the rule as the value of its
+        // block instead of assigning $$
+        diagnostics.retain(|d| d.code != Some("unset-value"));
+    }
+    if !diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        let generated = match language.as_str() {
+            "rust" => codegen::generate(&grammar),
+            "c" => bison::generate(&grammar),
+            "header" => Ok(bison::header(&grammar)),
+            _ => return usage(),
+        };
+        match generated {
+            Ok(source) => match output {
+                Some(output) => {
+                    std::fs::write(output, source).expect("Failed to write output file")
+                }
+                None => print!("{}", source),
+            },
+            Err(errors) => diagnostics.extend(errors),
+        }
+    }
+
+    report(input_file, &input, &diagnostics);
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        1
+    } else {
+        0
+    }
+}
+
+// The formats export writes, JSON only with the serde feature
+enum Format {
+    #[cfg(feature = "serde")]
+    Json,
+    Ebnf(Dialect),
+}
+
+// Exports the parsed grammar for other tools, written to the output file or
+// else to stdout. Only syntax errors prevent the export.
+fn export(args: &[String]) -> i32 {
+    let (format, output, input_file) = match args {
+        [flag, format, flag2, output, input_file] if flag == "--format" && flag2 == "-o" => {
+            (format, Some(output), input_file)
+        }
+        [flag, format, input_file] if flag == "--format" && !input_file.starts_with('-') => {
+            (format, None, input_file)
+        }
+        _ => return usage(),
+    };
+    let format = match format.as_str() {
+        #[cfg(feature = "serde")]
+        "json" => Format::Json,
+        #[cfg(not(feature = "serde"))]
+        "json" => {
+            eprintln!("yacc-parser was built without JSON support, enable the serde feature");
+            return 2;
+        }
+        "ebnf" => Format::Ebnf(Dialect::W3c),
+        "iso-ebnf" => Format::Ebnf(Dialect::Iso),
+        _ => return usage(),
+    };
+    let input = read(input_file);
+    let mut parser = parser::Parser::new(&input, Lexer::new(&input));
+    let (grammar, diagnostics) = parser.parse_grammar_recovering();
+    if diagnostics.is_empty() {
+        let exported = match format {
+            #[cfg(feature = "serde")]
+            Format::Json => export::to_json(&grammar) + "\n",
+            Format::Ebnf(dialect) => export::to_ebnf(&grammar, dialect),
+        };
+        match output {
+            Some(output) => std::fs::write(output, exported).expect("Failed to write output file"),
+            None => print!("{}", exported),
+        }
+    }
+
+    report(input_file, &input, &diagnostics);
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        1
+    } else {
+        0
+    }
+}
+
+// Formats the files in place, or with --check only reports the files which
+// are not formatted.
+fn fmt(args: &[String]) -> i32 {
+    let mut options = FormatOptions::default();
+    let mut check = false;
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--align-actions" => options.align_actions = true,
+            "--strip-comments" => options.preserve_comments = false,
+            "--indent" | "--max-width" => {
+                let Some(value) = args.next().and_then(|v| v.parse().ok()) else {
+                    return usage();
+                };
+                if arg == "--indent" {
+                    options.indent = value;
+                } else {
+                    options.max_width = value;
+                }
+            }
+            "--first-alternative" => {
+                options.first_alternative = match args.next().map(String::as_str) {
+                    Some("colon") => FirstAlternative::Colon,
+                    Some("same-line") => FirstAlternative::SameLine,
+                    Some("bar") => FirstAlternative::Bar,
+                    _ => return usage(),
+                }
+            }
+            _ if arg.starts_with("--") => return usage(),
+            _ => files.push(arg.clone()),
+        }
+    }
+    if files.is_empty() {
+        return usage();
+    }
+
+    let mut code = 0;
+    for input_file in files {
+        let input = read(&input_file);
+        match yacc_parser::format::format_source(&input, &options) {
+            Ok(formatted) if formatted == input => {}
+            Ok(formatted) => {
+                if check {
+                    println!("{} is not formatted", input_file);
+                    code = 1;
+                } else {
+                    std::fs::write(&input_file, formatted).expect("Failed to write input file");
+                }
+            }
+            Err(err) => {
+                report(&input_file, &input, &[Diagnostic::from(err)]);
+                code = 1;
+            }
+        }
+    }
+    code
 }
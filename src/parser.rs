@@ -1,141 +1,199 @@
+use crate::action::Action;
+use crate::diagnostics::Diagnostic;
+use crate::error::ParseError;
+use crate::escape;
 use crate::grammar::Alternative;
 use crate::grammar::Directive;
+use crate::grammar::Element;
 use crate::grammar::Grammar;
 use crate::grammar::Rule;
+use crate::grammar::TokenDeclaration;
 use crate::lexer::Lexer;
 use crate::token::Spanned;
 use crate::token::Token;
 
-pub struct Parser<'a> {
+pub struct Parser<'a, I: Iterator<Item = Spanned<Token>> = Lexer<'a>> {
     input: &'a str,
-    lexer: std::iter::Peekable<Lexer<'a>>,
+    lexer: std::iter::Peekable<I>,
+    eof: Spanned<Token>,
+    // A token pushed back by error recovery, returned before the lexer is
+    // consulted again.
+    pending: Option<Spanned<Token>>,
+    errors: Vec<ParseError>,
+    // End of the last consumed token
+    last_end: usize,
 }
 
-impl<'a> Parser<'a> {
-    pub fn new(input: &'a str, lexer: Lexer<'a>) -> Self {
+impl<'a, I: Iterator<Item = Spanned<Token>>> Parser<'a, I> {
+    // The parser accepts any token stream, normally a Lexer over the same
+    // input. Whitespace and Comment tokens must not be part of it.
+    pub fn new(input: &'a str, lexer: I) -> Self {
         Parser {
             input,
             lexer: lexer.peekable(),
+            eof: Spanned::new(Token::Eof, input.len()..input.len()),
+            pending: None,
+            errors: Vec::new(),
+            last_end: 0,
         }
     }
 
     fn next(&mut self) -> Spanned<Token> {
-        self.debug("next");
-        self.lexer.next().unwrap()
+        let token = match self.pending.take() {
+            Some(pending) => pending,
+            None => self.lexer.next().unwrap_or_else(|| self.eof.clone()),
+        };
+        self.last_end = token.span.end;
+        token
     }
 
     fn peek(&mut self) -> &Spanned<Token> {
-        self.debug("peek");
-        self.lexer.peek().unwrap()
+        if let Some(pending) = &self.pending {
+            return pending;
+        }
+        self.lexer.peek().unwrap_or(&self.eof)
     }
 
-    #[allow(dead_code)]
-    fn debug(&mut self, s: &str) {
-        let peek = self.lexer.peek().unwrap().clone();
-        let source = self.input[peek.span.clone().start..]
-            .chars()
-            .take(200)
-            .collect::<String>();
-        println!(
-            "Peek: [{s}] {:?} ({}) at {}",
-            peek.data,
-            self.text(peek.clone()),
-            source
-        );
+    fn unread(&mut self, token: Spanned<Token>) {
+        debug_assert!(self.pending.is_none());
+        self.pending = Some(token);
     }
 
     fn text(&self, spanned: Spanned<Token>) -> &str {
         &self.input[spanned.span.clone()]
     }
 
-    fn expect(&mut self, token: Token) -> Spanned<Token> {
-        let spanned = self.next();
-        if spanned.data != token {
-            panic!(
-                "Expected {:?}, found {:?} ({}) at byte {:?}",
-                token,
-                spanned.data,
-                self.text(spanned.clone()),
-                spanned.span.start
-            )
+    // The text of a `{ ... }` token without the braces
+    fn code_text(&self, spanned: &Spanned<Token>) -> String {
+        self.input[spanned.span.start + 1..spanned.span.end - 1].to_string()
+    }
+
+    fn spanned_text(&self, spanned: &Spanned<Token>) -> Spanned<String> {
+        Spanned::new(
+            self.input[spanned.span.clone()].to_string(),
+            spanned.span.clone(),
+        )
+    }
+
+    fn error(&self, token: Spanned<Token>, expected: Vec<Token>) -> ParseError {
+        let expected_text = expected
+            .iter()
+            .map(|t| format!("{:?}", t))
+            .collect::<Vec<_>>()
+            .join(" or ");
+        let message = format!(
+            "Expected {}, found {:?} ({})",
+            expected_text,
+            token.data,
+            self.text(token.clone())
+        );
+        ParseError::new(token, expected, message)
+    }
+
+    // Consumes the next token if it is of the expected kind. On mismatch the
+    // token is left in place so error recovery can resynchronise on it.
+    fn expect(&mut self, token: Token) -> Result<Spanned<Token>, ParseError> {
+        if self.peek().data != token {
+            let found = self.peek().clone();
+            return Err(self.error(found, vec![token]));
         }
-        spanned
+        Ok(self.next())
     }
 
-    fn parse_head(&mut self) -> (Vec<Directive>, Vec<String>) {
-        let mut directives = Vec::new();
-        let mut prologues = Vec::new();
+    fn parse_head(&mut self, grammar: &mut Grammar) -> Result<(), ParseError> {
         loop {
             match self.peek().data {
-                Token::Directive => directives.push(self.parse_directive()),
-                Token::Prologue => prologues.push(self.parse_prologue()),
+                Token::Directive => grammar.directives.push(self.parse_directive()?),
+                Token::Prologue => grammar.prologues.push(self.parse_prologue()?),
                 _ => break,
             }
         }
-        (directives, prologues)
+        Ok(())
     }
 
-    fn parse_directive(&mut self) -> Directive {
-        let directive = self.expect(Token::Directive);
-        match &self.input[directive.span.clone()] {
+    // The number of conflicts given to %expect or %expect-rr
+    fn parse_count(&mut self, keyword: &str) -> Result<i32, ParseError> {
+        let number = self.expect(Token::Number)?;
+        self.text(number.clone()).parse().map_err(|_| {
+            ParseError::new(
+                number.clone(),
+                vec![],
+                format!("Invalid {} count '{}'", keyword, self.text(number.clone())),
+            )
+        })
+    }
+
+    fn parse_directive(&mut self) -> Result<Spanned<Directive>, ParseError> {
+        let directive = self.expect(Token::Directive)?;
+        let start = directive.span.start;
+        let directive = match &self.input[directive.span.clone()] {
             "%pure-parser" => Directive::PureParser,
-            "%expect" => {
-                let number = self.expect(Token::Number);
-                Directive::Expect {
-                    number: self.text(number).parse().unwrap(),
-                }
-            }
+            "%expect" => Directive::Expect {
+                number: self.parse_count("%expect")?,
+            },
+            "%expect-rr" => Directive::ExpectRr {
+                number: self.parse_count("%expect-rr")?,
+            },
             "%name-prefix" => {
-                self.expect(Token::Equal);
-                let prefix = self.expect(Token::String);
+                self.expect(Token::Equal)?;
+                let prefix = self.expect(Token::String)?;
                 Directive::NamePrefix {
-                    prefix: self.text(prefix).to_string(),
+                    prefix: self.input[prefix.span.start + 1..prefix.span.end - 1].to_string(),
                 }
             }
             "%locations" => Directive::Locations,
             "%parse-param" => {
-                let params = self.expect(Token::Code);
+                let params = self.expect(Token::Code)?;
                 Directive::ParseParam {
-                    params: self.input[params.span.clone()].to_string(),
+                    params: self.code_text(&params),
                 }
             }
             "%lex-param" => {
-                let program = self.expect(Token::Code);
+                let program = self.expect(Token::Code)?;
                 Directive::LexProgram {
-                    params: self.input[program.span.clone()].to_string(),
+                    params: self.code_text(&program),
                 }
             }
             "%union" => {
-                let code = self.expect(Token::Code);
+                let code = self.expect(Token::Code)?;
                 Directive::Union {
-                    code: self.input[code.span.clone()].to_string(),
+                    code: self.code_text(&code),
                 }
             }
             "%type" => {
-                let type_name = self.expect(Token::Type);
+                let type_name = self.expect(Token::Type)?;
                 let mut rule_names = Vec::new();
                 loop {
                     if !matches!(self.peek().data, Token::Ident) {
                         break;
                     }
-                    let rule_name = self.expect(Token::Ident);
-                    rule_names.push(self.input[rule_name.span.clone()].to_string());
+                    let rule_name = self.expect(Token::Ident)?;
+                    rule_names.push(self.spanned_text(&rule_name));
                 }
                 Directive::Type {
-                    type_name: self.input[type_name.span.clone()].to_string(),
+                    type_name: self.spanned_text(&type_name),
                     rule_names,
                 }
             }
             "%token" => {
                 let token_name = if self.peek().data == Token::Type {
-                    let token_name = self.expect(Token::Type);
-                    Some(self.input[token_name.span.clone()].to_string())
+                    let token_name = self.expect(Token::Type)?;
+                    Some(self.spanned_text(&token_name))
                 } else {
                     None
                 };
                 let mut rule_names = Vec::new();
-                while let Some(ident) = self.rule_name() {
-                    rule_names.push(ident);
+                while self.peek().data != Token::String {
+                    let Some(name) = self.rule_name()? else {
+                        break;
+                    };
+                    let alias = if self.peek().data == Token::String {
+                        let alias = self.expect(Token::String)?;
+                        Some(self.string_element(alias)?.text().clone())
+                    } else {
+                        None
+                    };
+                    rule_names.push(TokenDeclaration { name, alias });
                 }
                 Directive::Token {
                     token_name,
@@ -144,135 +202,271 @@ impl<'a> Parser<'a> {
             }
             "%left" => {
                 let mut rule_names = Vec::new();
-                while let Some(ident) = self.rule_name() {
+                while let Some(ident) = self.rule_name()? {
                     rule_names.push(ident);
                 }
                 Directive::Left { rule_names }
             }
             "%right" => {
                 let mut rule_names = Vec::new();
-                while let Some(ident) = self.rule_name() {
+                while let Some(ident) = self.rule_name()? {
                     rule_names.push(ident);
                 }
                 Directive::Right { rule_names }
             }
             "%nonassoc" => {
                 let mut rule_names = Vec::new();
-                while let Some(ident) = self.rule_name() {
+                while let Some(ident) = self.rule_name()? {
                     rule_names.push(ident);
                 }
                 Directive::NonAssoc { rule_names }
             }
-            t => panic!("Unknown directive '{t}'"),
-        }
+            t => {
+                return Err(ParseError::new(
+                    directive.clone(),
+                    vec![],
+                    format!("Unknown directive '{t}'"),
+                ))
+            }
+        };
+        Ok(Spanned::new(directive, start..self.last_end))
     }
 
-    fn parse_prologue(&mut self) -> String {
-        let prologue = self.expect(Token::Prologue);
-        self.input[prologue.span.start + 2..prologue.span.end - 1].to_string()
+    fn parse_prologue(&mut self) -> Result<Spanned<String>, ParseError> {
+        let prologue = self.expect(Token::Prologue)?;
+        Ok(Spanned::new(
+            self.input[prologue.span.start + 2..prologue.span.end - 2].to_string(),
+            prologue.span,
+        ))
     }
 
-    fn rule_name(&mut self) -> Option<String> {
+    fn rule_name(&mut self) -> Result<Option<Spanned<String>>, ParseError> {
         match self.peek().data {
             Token::Ident => {
-                let ident = self.expect(Token::Ident);
-                Some(self.input[ident.span.clone()].to_string())
+                let ident = self.expect(Token::Ident)?;
+                Ok(Some(self.spanned_text(&ident)))
             }
             Token::Char => {
-                let char = self.expect(Token::Char);
-                Some(self.input[char.span.clone()].to_string())
+                let char = self.expect(Token::Char)?;
+                Ok(Some(self.char_element(char)?.text().clone()))
+            }
+            Token::String => {
+                let string = self.expect(Token::String)?;
+                Ok(Some(self.string_element(string)?.text().clone()))
             }
-            _ => None,
+            _ => Ok(None),
         }
     }
 
-    fn parse_rule(&mut self) -> Rule {
-        let name_token = self.expect(Token::Ident);
-        let name = self.input[name_token.span.clone()].to_string();
-        self.expect(Token::Colon);
+    fn char_element(&self, char: Spanned<Token>) -> Result<Element, ParseError> {
+        let text = self.spanned_text(&char);
+        match escape::char_value(&text.data) {
+            Some(value) => Ok(Element::Char { text, value }),
+            None => Err(ParseError::new(
+                char,
+                vec![],
+                format!("Invalid character literal {}", text.data),
+            )),
+        }
+    }
+
+    fn string_element(&self, string: Spanned<Token>) -> Result<Element, ParseError> {
+        let text = self.spanned_text(&string);
+        match escape::string_value(&text.data) {
+            Some(value) => Ok(Element::String { text, value }),
+            None => Err(ParseError::new(
+                string,
+                vec![],
+                format!("Invalid string literal {}", text.data),
+            )),
+        }
+    }
+
+    fn parse_rule(&mut self) -> Result<Rule, ParseError> {
+        let name_token = self.expect(Token::Ident)?;
+        let name = self.spanned_text(&name_token);
+        self.expect(Token::Colon)?;
 
         let mut alternatives = Vec::new();
         loop {
+            let start = self.peek().span.start;
             let mut elements = Vec::new();
             loop {
                 match self.peek().data {
                     Token::Ident => {
-                        let element = self.expect(Token::Ident);
-                        elements.push(self.input[element.span.clone()].to_string());
+                        let element = self.expect(Token::Ident)?;
+                        if self.peek().data == Token::Colon {
+                            // The start of the next rule, so the ';' is missing.
+                            self.unread(element.clone());
+                            return Err(self.error(element, vec![Token::Bar, Token::SemiColon]));
+                        }
+                        elements.push(Element::Symbol(self.spanned_text(&element)));
                     }
                     Token::Char => {
-                        let char = self.expect(Token::Char);
-                        elements.push(self.input[char.span.clone()].to_string());
+                        let char = self.expect(Token::Char)?;
+                        elements.push(self.char_element(char)?);
+                    }
+                    Token::String => {
+                        let string = self.expect(Token::String)?;
+                        elements.push(self.string_element(string)?);
+                    }
+                    Token::Code => {
+                        let code = self.expect(Token::Code)?;
+                        elements.push(Element::Action(Action::new(self.spanned_text(&code))));
                     }
                     _ => break,
                 }
             }
 
             let precedence = if let Token::Directive = self.peek().data {
-                let directive = &self.input[self.expect(Token::Directive).span.clone()];
-                if directive != "%prec" {
-                    panic!("Excepted %prec, got {}", directive);
+                let directive = self.expect(Token::Directive)?;
+                if self.text(directive.clone()) != "%prec" {
+                    return Err(ParseError::new(
+                        directive.clone(),
+                        vec![Token::Directive],
+                        format!("Expected %prec, found {}", self.text(directive)),
+                    ));
                 }
-                let prec = self.expect(Token::Ident);
-                Some(self.input[prec.span.clone()].to_string())
+                let prec = self.expect(Token::Ident)?;
+                Some(self.spanned_text(&prec))
             } else {
                 None
             };
 
+            // The last action is the alternative's action, whether it comes
+            // before or after %prec
             let action = if let Token::Code = self.peek().data {
-                let code = self.expect(Token::Code);
-                Some(self.input[code.span.clone()].to_string())
+                let code = self.expect(Token::Code)?;
+                Some(Action::new(self.spanned_text(&code)))
             } else {
-                None
+                match elements.pop() {
+                    Some(Element::Action(action)) => Some(action),
+                    last => {
+                        elements.extend(last);
+                        None
+                    }
+                }
             };
 
+            let end = self.last_end.max(start);
             alternatives.push(Alternative {
                 elements,
                 precedence,
                 action,
+                span: start..end,
             });
 
             // Check if there are more alternatives
             match self.peek().data {
                 Token::Bar => {
-                    self.expect(Token::Bar);
+                    self.expect(Token::Bar)?;
                 }
                 Token::SemiColon => {
-                    self.expect(Token::SemiColon);
+                    self.expect(Token::SemiColon)?;
                     break;
                 }
-                _ => panic!("Expected '|' or ';', found {:?}", self.peek().data),
+                _ => {
+                    let found = self.peek().clone();
+                    return Err(self.error(found, vec![Token::Bar, Token::SemiColon]));
+                }
             }
         }
 
-        Rule { name, alternatives }
+        Ok(Rule {
+            name,
+            alternatives,
+            span: name_token.span.start..self.last_end,
+        })
     }
 
+    // Parses rules until the epilogue. A malformed rule is dropped, its error
+    // recorded and parsing resumes at the next rule.
     fn parse_rules(&mut self) -> Vec<Rule> {
         let mut rules = Vec::new();
-        while let Token::Ident = self.peek().data {
-            rules.push(self.parse_rule());
+        loop {
+            let result = match self.peek().data {
+                Token::Epilogue | Token::Eof => break,
+                Token::Ident => self.parse_rule(),
+                _ => {
+                    let found = self.peek().clone();
+                    Err(self.error(found, vec![Token::Ident, Token::Epilogue]))
+                }
+            };
+            match result {
+                Ok(rule) => rules.push(rule),
+                Err(err) => {
+                    self.errors.push(err);
+                    self.synchronize_rule();
+                }
+            }
         }
         rules
     }
 
-    fn parse_epilogue(&mut self) -> String {
-        let epilogue = self.expect(Token::Epilogue);
-        //self.expect(Token::Eof); // TODO: Broken
-        self.input[epilogue.span.start + 2..epilogue.span.end].to_string()
+    // Skips tokens until just after the next ';', or until the next `Ident :`
+    // pair which starts a new rule.
+    fn synchronize_rule(&mut self) {
+        loop {
+            match self.peek().data {
+                Token::Epilogue | Token::Eof => return,
+                Token::SemiColon => {
+                    self.next();
+                    return;
+                }
+                Token::Ident => {
+                    let ident = self.next();
+                    if self.peek().data == Token::Colon {
+                        self.unread(ident);
+                        return;
+                    }
+                }
+                _ => {
+                    self.next();
+                }
+            }
+        }
     }
 
-    pub fn parse_grammar(&mut self) -> Grammar {
-        let (directives, prologues) = self.parse_head();
-        self.expect(Token::PercentPercent);
-        let rules = self.parse_rules();
-        let epilogue = self.parse_epilogue();
+    fn parse_epilogue(&mut self) -> Result<Spanned<String>, ParseError> {
+        let epilogue = self.expect(Token::Epilogue)?;
+        self.expect(Token::Eof)?;
+        Ok(Spanned::new(
+            self.input[epilogue.span.start + 2..epilogue.span.end].to_string(),
+            epilogue.span,
+        ))
+    }
 
-        Grammar {
-            directives,
-            rules,
-            prologues,
-            epilogue,
+    fn parse_sections(&mut self, grammar: &mut Grammar) -> Result<(), ParseError> {
+        self.parse_head(grammar)?;
+        self.expect(Token::PercentPercent)?;
+        grammar.rules = self.parse_rules();
+        grammar.epilogue = self.parse_epilogue()?;
+        Ok(())
+    }
+
+    fn parse(&mut self) -> (Grammar, Vec<ParseError>) {
+        let mut grammar = Grammar::default();
+        if let Err(err) = self.parse_sections(&mut grammar) {
+            self.errors.push(err);
+        }
+        (grammar, std::mem::take(&mut self.errors))
+    }
+
+    // Parses the whole file, failing on the first syntax error.
+    pub fn parse_grammar(&mut self) -> Result<Grammar, ParseError> {
+        let (grammar, mut errors) = self.parse();
+        if errors.is_empty() {
+            Ok(grammar)
+        } else {
+            Err(errors.remove(0))
         }
     }
+
+    // Parses the whole file, recovering from syntax errors in the rules
+    // section. Returns the rules which could be parsed together with a
+    // diagnostic for every error found.
+    pub fn parse_grammar_recovering(&mut self) -> (Grammar, Vec<Diagnostic>) {
+        let (grammar, errors) = self.parse();
+        (grammar, errors.into_iter().map(Diagnostic::from).collect())
+    }
 }
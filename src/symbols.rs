@@ -0,0 +1,437 @@
+// The symbols of a grammar. Names in rules and directives are resolved to
+// interned symbols, which know whether they are tokens or nonterminals and
+// what the directives declared about them.
+use crate::diagnostics::Diagnostic;
+use crate::escape;
+use crate::grammar::Directive;
+use crate::grammar::Element;
+use crate::grammar::Grammar;
+use crate::token::Spanned;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::Range;
+
+// An index into the symbol table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    // A token declared with %token, %left, %right or %nonassoc, or a string
+    // literal used as a token
+    Terminal,
+    // A character literal token such as '+', and the character it denotes
+    Char(char),
+    // A symbol with rules
+    Nonterminal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+    NonAssoc,
+}
+
+// Precedence levels count the precedence directives from 1, so later ones
+// bind tighter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Precedence {
+    pub level: usize,
+    pub associativity: Associativity,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    // As written in the grammar, e.g. NUM, expr or '+'
+    pub name: String,
+    pub kind: SymbolKind,
+    // The string alias of a token, e.g. "+", as written
+    pub alias: Option<String>,
+    // The declared <type>, including the angle brackets
+    pub type_name: Option<String>,
+    pub precedence: Option<Precedence>,
+    // Where the symbol is first declared or defined, None for the predefined
+    // $end and error
+    pub span: Option<Range<usize>>,
+    // Indices into Grammar::rules of the rules defining a nonterminal. Rules
+    // with the same name add alternatives to the same nonterminal.
+    pub rules: Vec<usize>,
+}
+
+impl Symbol {
+    fn new(name: &str, kind: SymbolKind, span: Option<Range<usize>>) -> Self {
+        Symbol {
+            name: name.to_string(),
+            kind,
+            alias: None,
+            type_name: None,
+            precedence: None,
+            span,
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.kind != SymbolKind::Nonterminal
+    }
+
+    // Whether the name can be used as is in generated C and Rust, which
+    // string literal tokens such as "+" cannot
+    pub fn is_identifier(&self) -> bool {
+        self.name
+            .starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+}
+
+// Tokens come first, starting with $end and error, then the nonterminals,
+// each group in the order they first appear.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    // Names and string aliases as written
+    ids: HashMap<String, SymbolId>,
+    start: Option<SymbolId>,
+}
+
+impl SymbolTable {
+    // The end of input marker
+    pub const END: SymbolId = SymbolId(0);
+    // The token yacc shifts when recovering from a syntax error
+    pub const ERROR: SymbolId = SymbolId(1);
+
+    // Builds the table, along with diagnostics for symbols which are used
+    // but never defined and for conflicting declarations.
+    pub fn new(grammar: &Grammar) -> (SymbolTable, Vec<Diagnostic>) {
+        let mut builder = Builder::default();
+        builder.intern(Symbol::new("$end", SymbolKind::Terminal, None));
+        builder.intern(Symbol::new("error", SymbolKind::Terminal, None));
+        builder.declare_tokens(grammar);
+        builder.define_rules(grammar);
+        builder.resolve_uses(grammar);
+        builder.declare_types(grammar);
+        builder.finish()
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    // Looks up a symbol by name, or a token by its string alias
+    pub fn get(&self, name: &str) -> Option<SymbolId> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id.0]
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = (SymbolId, &Symbol)> {
+        self.symbols
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (SymbolId(i), s))
+    }
+
+    pub fn terminals(&self) -> impl Iterator<Item = (SymbolId, &Symbol)> {
+        self.symbols().filter(|(_, s)| s.is_terminal())
+    }
+
+    pub fn nonterminals(&self) -> impl Iterator<Item = (SymbolId, &Symbol)> {
+        self.symbols().filter(|(_, s)| !s.is_terminal())
+    }
+
+    // The symbol an element of an alternative refers to, None for actions
+    pub fn element(&self, element: &Element) -> Option<SymbolId> {
+        match element {
+            Element::Action(_) => None,
+            _ => self.get(&element.text().data),
+        }
+    }
+
+    // The nonterminal of the first rule
+    pub fn start(&self) -> Option<SymbolId> {
+        self.start
+    }
+
+    // For the nonterminal $@N of each mid-rule action of a grammar with
+    // desugared mid-rule actions, its position in the enclosing alternative
+    // and the symbols before it, which its action reaches with $0 and below
+    pub fn mid_rule_contexts(
+        &self,
+        grammar: &Grammar,
+    ) -> HashMap<SymbolId, (usize, Vec<SymbolId>)> {
+        let mut contexts = HashMap::new();
+        for rule in &grammar.rules {
+            for alternative in &rule.alternatives {
+                for (i, element) in alternative.elements.iter().enumerate() {
+                    if !element.text().data.starts_with("$@") {
+                        continue;
+                    }
+                    if let Some(id) = self.element(element) {
+                        let before = alternative.elements[..i]
+                            .iter()
+                            .filter_map(|e| self.element(e))
+                            .collect();
+                        contexts.insert(id, (i, before));
+                    }
+                }
+            }
+        }
+        contexts
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    symbols: Vec<Symbol>,
+    ids: HashMap<String, SymbolId>,
+    // Tokens declared with %token
+    tokens: HashSet<SymbolId>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Builder {
+    fn intern(&mut self, symbol: Symbol) -> SymbolId {
+        let id = SymbolId(self.symbols.len());
+        self.ids.insert(symbol.name.clone(), id);
+        self.symbols.push(symbol);
+        id
+    }
+
+    // Interns a token named by a directive or used in a rule, classifying
+    // character literals by their value
+    fn intern_token(&mut self, name: &Spanned<String>) -> SymbolId {
+        let kind = match escape::char_value(&name.data) {
+            Some(value) => SymbolKind::Char(value),
+            None => SymbolKind::Terminal,
+        };
+        self.intern(Symbol::new(&name.data, kind, Some(name.span.clone())))
+    }
+
+    // A note pointing at the earlier declaration of a symbol
+    fn previous(&self, id: SymbolId, diagnostic: Diagnostic, what: &str) -> Diagnostic {
+        match &self.symbols[id.0].span {
+            Some(span) => diagnostic.with_secondary_label(span.clone(), what.to_string()),
+            None => diagnostic.with_note(format!("{} is predefined", self.symbols[id.0].name)),
+        }
+    }
+
+    fn declare_tokens(&mut self, grammar: &Grammar) {
+        let mut level = 0;
+        for directive in &grammar.directives {
+            let (names, associativity) = match &directive.data {
+                Directive::Token {
+                    token_name,
+                    rule_names,
+                } => {
+                    for declaration in rule_names {
+                        let id = self.declare_token(&declaration.name);
+                        if let Some(type_name) = token_name {
+                            self.set_type(id, type_name, &declaration.name.span);
+                        }
+                        if let Some(alias) = &declaration.alias {
+                            self.set_alias(id, alias);
+                        }
+                    }
+                    continue;
+                }
+                Directive::Left { rule_names } => (rule_names, Associativity::Left),
+                Directive::Right { rule_names } => (rule_names, Associativity::Right),
+                Directive::NonAssoc { rule_names } => (rule_names, Associativity::NonAssoc),
+                _ => continue,
+            };
+            level += 1;
+            let precedence = Precedence {
+                level,
+                associativity,
+            };
+            for name in names {
+                let id = match self.ids.get(&name.data) {
+                    Some(id) => *id,
+                    None => self.intern_token(name),
+                };
+                match self.symbols[id.0].precedence {
+                    Some(_) => {
+                        let diagnostic = Diagnostic::error(
+                            format!("{} redeclaration for {}", directive.data.keyword(), name),
+                            name.span.clone(),
+                        );
+                        let diagnostic = self.previous(id, diagnostic, "first declared here");
+                        self.diagnostics.push(diagnostic);
+                    }
+                    None => self.symbols[id.0].precedence = Some(precedence),
+                }
+            }
+        }
+    }
+
+    fn declare_token(&mut self, name: &Spanned<String>) -> SymbolId {
+        let id = match self.ids.get(&name.data) {
+            Some(&id) => id,
+            None => self.intern_token(name),
+        };
+        // Giving a token precedence declares it too, so only a second %token
+        // is suspicious
+        if !self.tokens.insert(id) {
+            let diagnostic =
+                Diagnostic::warning(format!("symbol {} redeclared", name), name.span.clone());
+            let diagnostic = self.previous(id, diagnostic, "first declared here");
+            self.diagnostics.push(diagnostic);
+        }
+        id
+    }
+
+    fn set_type(&mut self, id: SymbolId, type_name: &Spanned<String>, span: &Range<usize>) {
+        if self.symbols[id.0].type_name.is_some() {
+            let diagnostic = Diagnostic::error(
+                format!("type redeclaration for {}", self.symbols[id.0].name),
+                span.clone(),
+            );
+            let diagnostic = self.previous(id, diagnostic, "first declared here");
+            self.diagnostics.push(diagnostic);
+            return;
+        }
+        self.symbols[id.0].type_name = Some(type_name.data.clone());
+    }
+
+    fn set_alias(&mut self, id: SymbolId, alias: &Spanned<String>) {
+        if let Some(&other) = self.ids.get(&alias.data) {
+            let diagnostic = Diagnostic::error(
+                format!("string {} used for two tokens", alias),
+                alias.span.clone(),
+            );
+            let diagnostic = self.previous(other, diagnostic, "already used here");
+            self.diagnostics.push(diagnostic);
+            return;
+        }
+        if let Some(previous) = &self.symbols[id.0].alias {
+            let message = format!(
+                "token {} already has the alias {}",
+                self.symbols[id.0].name, previous
+            );
+            self.diagnostics
+                .push(Diagnostic::error(message, alias.span.clone()));
+            return;
+        }
+        self.symbols[id.0].alias = Some(alias.data.clone());
+        self.ids.insert(alias.data.clone(), id);
+    }
+
+    fn define_rules(&mut self, grammar: &Grammar) {
+        for (i, rule) in grammar.rules.iter().enumerate() {
+            let id = match self.ids.get(&rule.name.data) {
+                Some(&id) if self.symbols[id.0].is_terminal() => {
+                    let diagnostic = Diagnostic::error(
+                        format!("rule given for {}, which is a token", rule.name),
+                        rule.name.span.clone(),
+                    );
+                    let diagnostic = self.previous(id, diagnostic, "declared as a token here");
+                    self.diagnostics.push(diagnostic);
+                    continue;
+                }
+                Some(&id) => id,
+                None => self.intern(Symbol::new(
+                    &rule.name.data,
+                    SymbolKind::Nonterminal,
+                    Some(rule.name.span.clone()),
+                )),
+            };
+            self.symbols[id.0].rules.push(i);
+        }
+    }
+
+    // Interns the literals used in rules, and reports names which are
+    // neither tokens nor have rules
+    fn resolve_uses(&mut self, grammar: &Grammar) {
+        for rule in &grammar.rules {
+            for alternative in &rule.alternatives {
+                let elements = alternative.elements.iter().filter_map(|e| match e {
+                    Element::Action(_) => None,
+                    e => Some(e.text()),
+                });
+                for name in elements.chain(&alternative.precedence) {
+                    if self.ids.contains_key(&name.data) {
+                        continue;
+                    }
+                    if name.data.starts_with(['\'', '"']) {
+                        self.intern_token(name);
+                        continue;
+                    }
+                    self.diagnostics.push(
+                        Diagnostic::error(
+                            format!(
+                                "symbol {} is used, but is not defined as a token and has no rules",
+                                name
+                            ),
+                            name.span.clone(),
+                        )
+                        .with_code("undefined-symbol")
+                        .with_primary_label("not defined".to_string()),
+                    );
+                    // Interned so that it is only reported once
+                    self.intern(Symbol::new(
+                        &name.data,
+                        SymbolKind::Nonterminal,
+                        Some(name.span.clone()),
+                    ));
+                }
+            }
+        }
+    }
+
+    // Applies %type, which names symbols rather than declaring them
+    fn declare_types(&mut self, grammar: &Grammar) {
+        for directive in &grammar.directives {
+            if let Directive::Type {
+                type_name,
+                rule_names,
+            } = &directive.data
+            {
+                for name in rule_names {
+                    if let Some(&id) = self.ids.get(&name.data) {
+                        self.set_type(id, type_name, &name.span);
+                    }
+                }
+            }
+        }
+    }
+
+    // Orders the tokens before the nonterminals
+    fn finish(self) -> (SymbolTable, Vec<Diagnostic>) {
+        let mut order = (0..self.symbols.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| !self.symbols[i].is_terminal());
+        let mut renumbered = vec![SymbolId(0); self.symbols.len()];
+        for (new, &old) in order.iter().enumerate() {
+            renumbered[old] = SymbolId(new);
+        }
+        let ids = self
+            .ids
+            .into_iter()
+            .map(|(name, id)| (name, renumbered[id.0]))
+            .collect();
+        let mut symbols = self.symbols.into_iter().map(Some).collect::<Vec<_>>();
+        let symbols = order
+            .iter()
+            .map(|&i| symbols[i].take().unwrap())
+            .collect::<Vec<_>>();
+        let start = symbols
+            .iter()
+            .position(|s| s.rules.first() == Some(&0))
+            .map(SymbolId);
+        let table = SymbolTable {
+            symbols,
+            ids,
+            start,
+        };
+        (table, self.diagnostics)
+    }
+}
@@ -0,0 +1,334 @@
+// Parse tables of an LR automaton. Conflicts between shifting and reducing
+// are resolved by the precedence of the token and of the production, as
+// declared with %left, %right and %nonassoc. The others are resolved like
+// yacc does, by shifting or by reducing by the earlier production, and are
+// reported.
+use crate::counterexample::Counterexample;
+use crate::counterexample::Explainer;
+use crate::diagnostics::Diagnostic;
+use crate::grammar::Directive;
+use crate::grammar::Grammar;
+use crate::lr::Automaton;
+use crate::symbols::Associativity;
+use crate::symbols::SymbolId;
+use crate::symbols::SymbolTable;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Shift(usize),
+    // By a production of the automaton
+    Reduce(usize),
+    Accept,
+    // A syntax error, where %nonassoc forbids both shifting and reducing
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    ShiftReduce,
+    ReduceReduce,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub state: usize,
+    pub token: SymbolId,
+    // The productions which could be reduced by, the chosen one first for a
+    // reduce/reduce conflict
+    pub productions: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tables {
+    // The action of each state on each token, where tokens without one are
+    // syntax errors
+    pub action: Vec<BTreeMap<SymbolId, Action>>,
+    // The state after reducing to each nonterminal
+    pub goto: Vec<BTreeMap<SymbolId, usize>>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Tables {
+    pub fn new(automaton: &Automaton, symbols: &SymbolTable) -> Self {
+        let mut tables = Tables {
+            action: Vec::new(),
+            goto: Vec::new(),
+            conflicts: Vec::new(),
+        };
+        let final_state = automaton.final_state();
+        for (i, state) in automaton.states.iter().enumerate() {
+            let mut shifts = BTreeMap::new();
+            let mut goto = BTreeMap::new();
+            for (&symbol, &next) in &state.transitions {
+                if automaton.is_terminal(symbol) {
+                    shifts.insert(symbol, Action::Shift(next));
+                } else {
+                    goto.insert(symbol, next);
+                }
+            }
+            if i == final_state {
+                shifts.insert(SymbolTable::END, Action::Accept);
+            }
+
+            // Precedence first takes tokens out of the lookaheads of the
+            // reductions, or takes their shifts away
+            let mut lookaheads = Vec::new();
+            let mut errors = BTreeSet::new();
+            for reduction in &state.reductions {
+                let mut lookahead = reduction.lookahead.clone();
+                if let Some(rule) = automaton.precedence[reduction.production] {
+                    for token in &reduction.lookahead {
+                        if !shifts.contains_key(token) {
+                            continue;
+                        }
+                        let Some(precedence) = symbols.symbol(*token).precedence else {
+                            continue;
+                        };
+                        let reduce = match rule.level.cmp(&precedence.level) {
+                            Ordering::Greater => true,
+                            Ordering::Less => false,
+                            Ordering::Equal => {
+                                if precedence.associativity == Associativity::NonAssoc {
+                                    errors.insert(*token);
+                                    lookahead.remove(token);
+                                    shifts.remove(token);
+                                    continue;
+                                }
+                                precedence.associativity == Associativity::Left
+                            }
+                        };
+                        if reduce {
+                            shifts.remove(token);
+                        } else {
+                            lookahead.remove(token);
+                        }
+                    }
+                }
+                lookaheads.push((reduction.production, lookahead));
+            }
+
+            let mut action = shifts.clone();
+            let mut reductions = BTreeMap::<SymbolId, Vec<usize>>::new();
+            for (production, lookahead) in &lookaheads {
+                for &token in lookahead {
+                    reductions.entry(token).or_default().push(*production);
+                }
+            }
+            for (token, productions) in reductions {
+                if shifts.contains_key(&token) {
+                    tables.conflicts.push(Conflict {
+                        kind: ConflictKind::ShiftReduce,
+                        state: i,
+                        token,
+                        productions: productions.clone(),
+                    });
+                } else {
+                    action.insert(token, Action::Reduce(productions[0]));
+                }
+                if productions.len() > 1 {
+                    tables.conflicts.push(Conflict {
+                        kind: ConflictKind::ReduceReduce,
+                        state: i,
+                        token,
+                        productions,
+                    });
+                }
+            }
+            for token in errors {
+                action.entry(token).or_insert(Action::Error);
+            }
+            tables.action.push(action);
+            tables.goto.push(goto);
+        }
+        tables
+    }
+
+    // The number of conflicts of a kind, counted as Bison does for %expect:
+    // a token with n reductions in a state makes n - 1 reduce/reduce
+    // conflicts
+    pub fn count(&self, kind: ConflictKind) -> usize {
+        self.conflicts
+            .iter()
+            .filter(|c| c.kind == kind)
+            .map(|c| match kind {
+                ConflictKind::ShiftReduce => 1,
+                ConflictKind::ReduceReduce => c.productions.len() - 1,
+            })
+            .sum()
+    }
+}
+
+// The number of conflicts of a kind the grammar expects, and the directive
+// which says so. Like Bison, %expect without %expect-rr expects no
+// reduce/reduce conflicts. The number is as written, which check_expectations
+// reports if negative.
+pub fn expected(grammar: &Grammar, kind: ConflictKind) -> Option<(i32, Range<usize>)> {
+    let find = |kind: ConflictKind| {
+        grammar
+            .directives
+            .iter()
+            .find_map(|d| match (kind, &d.data) {
+                (ConflictKind::ShiftReduce, Directive::Expect { number })
+                | (ConflictKind::ReduceReduce, Directive::ExpectRr { number }) => {
+                    Some((*number, d.span.clone()))
+                }
+                _ => None,
+            })
+    };
+    match kind {
+        ConflictKind::ShiftReduce => find(kind),
+        ConflictKind::ReduceReduce => {
+            find(kind).or_else(|| find(ConflictKind::ShiftReduce).map(|(_, span)| (0, span)))
+        }
+    }
+}
+
+// An error for each kind of conflict whose number is not the one expected
+// by %expect or %expect-rr, or whose expected number is negative
+pub fn check_expectations(grammar: &Grammar, tables: &Tables) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for (kind, name, code, keyword) in [
+        (
+            ConflictKind::ShiftReduce,
+            "shift/reduce",
+            "conflicts-sr",
+            "%expect",
+        ),
+        (
+            ConflictKind::ReduceReduce,
+            "reduce/reduce",
+            "conflicts-rr",
+            "%expect-rr",
+        ),
+    ] {
+        let Some((number, span)) = expected(grammar, kind) else {
+            continue;
+        };
+        let found = tables.count(kind);
+        if number < 0 {
+            diagnostics.push(
+                Diagnostic::error(format!("invalid {} count: {}", keyword, number), span)
+                    .with_code(code)
+                    .with_primary_label("the number of conflicts cannot be negative".to_string()),
+            );
+        } else if found != number as usize {
+            diagnostics.push(
+                Diagnostic::error(
+                    format!("{} conflicts: {} found, {} expected", name, found, number),
+                    span,
+                )
+                .with_code(code),
+            );
+        }
+    }
+    diagnostics
+}
+
+// A warning for each conflict, after the errors of check_expectations.
+// Conflicts of a kind whose number is as expected are not reported one by
+// one.
+pub fn check_conflicts(
+    grammar: &Grammar,
+    symbols: &SymbolTable,
+    automaton: &Automaton,
+    tables: &Tables,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = check_expectations(grammar, tables);
+    // A negative expected number matches no count, so every conflict is
+    // reported
+    let as_expected = |kind| {
+        expected(grammar, kind)
+            .is_some_and(|(number, _)| usize::try_from(number) == Ok(tables.count(kind)))
+    };
+    let alternative = |production: usize| {
+        let production = &automaton.productions[production];
+        grammar.rules[production.rule].alternatives[production.alternative]
+            .span
+            .clone()
+    };
+    // Built only when there are conflicts to explain
+    let mut explainer = None;
+    for conflict in &tables.conflicts {
+        let token = &symbols.symbol(conflict.token).name;
+        let diagnostic = match conflict.kind {
+            kind if as_expected(kind) => continue,
+            ConflictKind::ShiftReduce => {
+                let mut diagnostic = Diagnostic::warning(
+                    format!("shift/reduce conflict on token {}", token),
+                    alternative(conflict.productions[0]),
+                )
+                .with_code("conflicts-sr")
+                .with_primary_label(format!("reduce by this alternative, or shift {}", token));
+                for &production in &conflict.productions[1..] {
+                    diagnostic = diagnostic
+                        .with_secondary_label(alternative(production), "or this one".to_string());
+                }
+                diagnostic.with_note("resolved by shifting".to_string())
+            }
+            ConflictKind::ReduceReduce => {
+                let mut diagnostic = Diagnostic::warning(
+                    format!("reduce/reduce conflict on token {}", token),
+                    alternative(conflict.productions[1]),
+                )
+                .with_code("conflicts-rr")
+                .with_primary_label("reduce by this alternative".to_string())
+                .with_secondary_label(
+                    alternative(conflict.productions[0]),
+                    "or by this one, which is chosen".to_string(),
+                );
+                for &production in &conflict.productions[2..] {
+                    diagnostic = diagnostic
+                        .with_secondary_label(alternative(production), "or this one".to_string());
+                }
+                diagnostic
+            }
+        };
+        let mut diagnostic = diagnostic.with_note(format!("in state {}", conflict.state));
+        let explainer =
+            explainer.get_or_insert_with(|| Explainer::new(grammar, symbols, automaton));
+        if let Some(counterexample) = explainer.explain(conflict) {
+            diagnostic = explain(diagnostic, explainer, conflict.kind, &counterexample);
+        }
+        diagnostics.push(diagnostic);
+    }
+    diagnostics
+}
+
+// Notes with the example and the derivations of a counterexample
+fn explain(
+    mut diagnostic: Diagnostic,
+    explainer: &Explainer,
+    kind: ConflictKind,
+    counterexample: &Counterexample,
+) -> Diagnostic {
+    let names = match kind {
+        ConflictKind::ShiftReduce => ["shift derivation", "reduce derivation"],
+        ConflictKind::ReduceReduce => ["first reduce derivation", "second reduce derivation"],
+    };
+    let [first, second] = &counterexample.derivations;
+    if counterexample.unifying {
+        diagnostic = diagnostic.with_note(format!("example: {}", explainer.example(first)));
+        for (name, derivation) in names.iter().zip([first, second]) {
+            diagnostic =
+                diagnostic.with_note(format!("{}: {}", name, explainer.render(derivation)));
+        }
+        return diagnostic.with_note(
+            "both derivations give the example, so the grammar is ambiguous".to_string(),
+        );
+    }
+    for ((name, derivation), which) in names.iter().zip([first, second]).zip(["first", "second"]) {
+        diagnostic = diagnostic
+            .with_note(format!(
+                "{} example: {}",
+                which,
+                explainer.example(derivation)
+            ))
+            .with_note(format!("{}: {}", name, explainer.render(derivation)));
+    }
+    diagnostic
+}
@@ -11,16 +11,19 @@ pub enum Token {
     Prologue,       // %{ ... %}
     Epilogue,       // Everything after the second %%
     Ident,          // [a-zA-Z_][a-zA-Z0-9_]*|'.'
-    String,         // '...'
+    String,         // "...", with C escapes
     Eof,            // End of file
     Directive,      // %ident ...
-    Char,           // 'a'
+    Char,           // 'a', '\n', '\x41'
     Number,         // 123
     Type,
+    Whitespace, // Only produced by Lexer::with_trivia
+    Comment,    // Only produced by Lexer::with_trivia
     Err,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Spanned<T> {
     pub data: T,
     pub span: std::ops::Range<usize>,
@@ -31,3 +34,9 @@ impl<T> Spanned<T> {
         Spanned { data, span }
     }
 }
+
+impl<T: std::fmt::Display> std::fmt::Display for Spanned<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.data.fmt(f)
+    }
+}
@@ -0,0 +1,241 @@
+// Splitting semantic actions into text and references.
+use proptest::prelude::*;
+use yacc_parser::action::Action;
+use yacc_parser::action::Fragment;
+use yacc_parser::action::Reference;
+use yacc_parser::action::ReferenceKind;
+use yacc_parser::action::Target;
+use yacc_parser::grammar::Grammar;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::parser::Parser;
+use yacc_parser::token::Spanned;
+
+fn action(code: &str) -> Action {
+    Action::new(Spanned::new(code.to_string(), 10..10 + code.len()))
+}
+
+fn value(target: Target) -> Reference {
+    Reference {
+        kind: ReferenceKind::Value,
+        type_tag: None,
+        target,
+    }
+}
+
+#[test]
+fn references() {
+    let code = r#"{ $$ = f($1, $-1, @$, @2, $<node>3, $expr, $[e.1]); /* $4 */ g("$5", '$'); }"#;
+    let action = action(code);
+    let references = action
+        .references()
+        .map(|r| (r.data.clone(), &code[r.span.start - 10..r.span.end - 10]))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        references,
+        vec![
+            (value(Target::Result), "$$"),
+            (value(Target::Index(1)), "$1"),
+            (value(Target::Index(-1)), "$-1"),
+            (
+                Reference {
+                    kind: ReferenceKind::Location,
+                    type_tag: None,
+                    target: Target::Result,
+                },
+                "@$"
+            ),
+            (
+                Reference {
+                    kind: ReferenceKind::Location,
+                    type_tag: None,
+                    target: Target::Index(2),
+                },
+                "@2"
+            ),
+            (
+                Reference {
+                    kind: ReferenceKind::Value,
+                    type_tag: Some("<node>".to_string()),
+                    target: Target::Index(3),
+                },
+                "$<node>3"
+            ),
+            (value(Target::Name("expr".to_string())), "$expr"),
+            (value(Target::BracketedName("e.1".to_string())), "$[e.1]"),
+        ]
+    );
+}
+
+#[test]
+fn rewrite() {
+    let action = action("{ $$ = $1 + $3; printf(\"$\"); x = a $ b; }");
+    let rewritten = action.rewrite(|reference| match reference.target {
+        Target::Result => Some("yyval".to_string()),
+        Target::Index(n) => Some(format!("yyvsp[{}]", n - 3)),
+        _ => None,
+    });
+    assert_eq!(
+        rewritten,
+        "{ yyval = yyvsp[-2] + yyvsp[0]; printf(\"$\"); x = a $ b; }"
+    );
+}
+
+#[test]
+fn unusual_indices() {
+    // Bison reads $01 as $1 and $-0 as $0, so they are references too, but
+    // they are kept as written when not rewritten
+    let action = action("{ $$ = $01 + $-0 + $-01; }");
+    let targets = action
+        .references()
+        .map(|r| r.data.target.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        targets,
+        vec![
+            Target::Result,
+            Target::Index(1),
+            Target::Index(0),
+            Target::Index(-1)
+        ]
+    );
+    assert_eq!(action.rewrite(|_| None), "{ $$ = $01 + $-0 + $-01; }");
+    let rewritten = action.rewrite(|reference| match reference.target {
+        Target::Index(n) => Some(format!("yyvsp[{}]", n - 2)),
+        _ => None,
+    });
+    assert_eq!(rewritten, "{ $$ = yyvsp[-1] + yyvsp[-2] + yyvsp[-3]; }");
+}
+
+#[test]
+fn lifetimes() {
+    // A Rust lifetime is not the start of a character literal
+    let action = action("{ let s: &'static str = $1; f::<'a>($2, 'c', '$') }");
+    let targets = action
+        .references()
+        .map(|r| r.data.target.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(targets, vec![Target::Index(1), Target::Index(2)]);
+}
+
+#[test]
+fn overflowing_indices() {
+    // Numbers which do not fit an i32 are still references, kept as written
+    let action = action("{ $$ = $99999999999 + @-99999999999 + $2147483647; }");
+    let targets = action
+        .references()
+        .map(|r| (r.data.target.clone(), r.data.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        targets,
+        vec![
+            (Target::Result, "$$".to_string()),
+            (
+                Target::OutOfRange("99999999999".to_string()),
+                "$99999999999".to_string()
+            ),
+            (
+                Target::OutOfRange("-99999999999".to_string()),
+                "@-99999999999".to_string()
+            ),
+            (Target::Index(i32::MAX), "$2147483647".to_string()),
+        ]
+    );
+}
+
+fn parse(input: &str) -> Grammar {
+    Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap()
+}
+
+#[test]
+fn desugar_mid_rule_actions() {
+    let input = "%%
+a: b { $$ = $1; } c { f($b, $2, @1); } d { $$ = $4; } ;
+b: { g(); } %prec X ;
+%%
+";
+    let desugared = parse(input).desugar_mid_rule_actions();
+    // The name, and the elements, precedence and action of each alternative
+    let rules = desugared
+        .rules
+        .iter()
+        .map(|rule| {
+            let alternatives = rule
+                .alternatives
+                .iter()
+                .map(|alternative| {
+                    (
+                        alternative
+                            .elements
+                            .iter()
+                            .map(|e| e.text().data.as_str())
+                            .collect::<Vec<_>>(),
+                        alternative.precedence.as_ref().map(|p| p.data.as_str()),
+                        alternative.action.as_ref().map(|a| a.code.data.as_str()),
+                    )
+                })
+                .collect::<Vec<_>>();
+            (rule.name.data.as_str(), alternatives)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rules,
+        vec![
+            (
+                "a",
+                vec![(
+                    vec!["b", "$@1", "c", "$@2", "d"],
+                    None,
+                    Some("{ $$ = $4; }")
+                )]
+            ),
+            ("b", vec![(vec![], Some("X"), Some("{ g(); }"))]),
+            ("$@1", vec![(vec![], None, Some("{ $$ = $0; }"))]),
+            ("$@2", vec![(vec![], None, Some("{ f($-2, $-1, @-2); }"))]),
+        ]
+    );
+    // The new rules and the symbols which replace the actions are placed
+    // where the actions were
+    for (n, code) in [(1, "{ $$ = $1; }"), (2, "{ f($b, $2, @1); }")] {
+        let start = input.find(code).unwrap();
+        let span = start..start + code.len();
+        let rule = &desugared.rules[n + 1];
+        assert_eq!(rule.span, span);
+        assert_eq!(rule.name.span, span);
+        assert_eq!(
+            desugared.rules[0].alternatives[0].elements[2 * n - 1].span(),
+            span
+        );
+    }
+}
+
+proptest! {
+    // The fragments cover the code exactly, and references print as
+    // something which parses back to them
+    #[test]
+    fn fragments_cover_code(code in "\\{[ a-z0-9$@<>\\[\\].\\-'\"/*\n]{0,40}\\}") {
+        let action = action(&code);
+        let mut end = 10;
+        let mut text = String::new();
+        for fragment in &action.fragments {
+            let span = match fragment {
+                Fragment::Text(t) => {
+                    prop_assert_eq!(&code[t.span.start - 10..t.span.end - 10], t.data.as_str());
+                    t.span.clone()
+                }
+                Fragment::Reference(r) => {
+                    let printed = self::action(&r.data.to_string());
+                    let reparsed = printed.references().map(|r| &r.data).collect::<Vec<_>>();
+                    prop_assert_eq!(reparsed, vec![&r.data]);
+                    r.span.clone()
+                }
+            };
+            prop_assert_eq!(span.start, end);
+            end = span.end;
+            text.push_str(&code[span.start - 10..span.end - 10]);
+        }
+        prop_assert_eq!(&text, &code);
+        prop_assert_eq!(action.rewrite(|_| None), code);
+    }
+}
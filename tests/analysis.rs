@@ -0,0 +1,83 @@
+// Nullable, FIRST and FOLLOW sets.
+use std::collections::BTreeSet;
+use yacc_parser::analysis::Analysis;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::parser::Parser;
+use yacc_parser::symbols::SymbolId;
+use yacc_parser::symbols::SymbolTable;
+
+const GRAMMAR: &str = "%token NUM
+%%
+expr: term rest ;
+rest: '+' term rest | ;
+term: factor { mid(); } factor_rest ;
+factor_rest: '*' factor factor_rest | ;
+factor: '(' expr ')' | NUM ;
+%%
+";
+
+fn analyse(input: &str) -> (SymbolTable, Analysis) {
+    let grammar = Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap();
+    let (symbols, diagnostics) = SymbolTable::new(&grammar);
+    assert!(diagnostics.is_empty());
+    let analysis = Analysis::new(&grammar, &symbols);
+    (symbols, analysis)
+}
+
+fn names(symbols: &SymbolTable, set: &BTreeSet<SymbolId>) -> Vec<String> {
+    set.iter()
+        .map(|&id| symbols.symbol(id).name.clone())
+        .collect()
+}
+
+#[test]
+fn nullable() {
+    let (symbols, analysis) = analyse(GRAMMAR);
+    let mut nullable = analysis
+        .nullable()
+        .iter()
+        .map(|&id| symbols.symbol(id).name.as_str())
+        .collect::<Vec<_>>();
+    nullable.sort();
+    assert_eq!(nullable, vec!["factor_rest", "rest"]);
+}
+
+#[test]
+fn first_and_follow() {
+    let (symbols, analysis) = analyse(GRAMMAR);
+    let first = |name| names(&symbols, analysis.first(symbols.get(name).unwrap()));
+    let follow = |name| names(&symbols, analysis.follow(symbols.get(name).unwrap()));
+    assert_eq!(first("expr"), vec!["NUM", "'('"]);
+    assert_eq!(first("rest"), vec!["'+'"]);
+    assert_eq!(first("factor_rest"), vec!["'*'"]);
+    assert_eq!(first("'+'"), vec!["'+'"]);
+    assert_eq!(follow("expr"), vec!["$end", "')'"]);
+    assert_eq!(follow("rest"), vec!["$end", "')'"]);
+    assert_eq!(follow("term"), vec!["$end", "'+'", "')'"]);
+    assert_eq!(follow("factor"), vec!["$end", "'+'", "'*'", "')'"]);
+}
+
+#[test]
+fn first_k() {
+    let (symbols, analysis) = analyse(GRAMMAR);
+    let sets = analysis.first_k(2);
+    let strings = |name| {
+        sets[&symbols.get(name).unwrap()]
+            .iter()
+            .map(|s| {
+                s.iter()
+                    .map(|&id| symbols.symbol(id).name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(strings("rest"), vec!["", "'+' NUM", "'+' '('"]);
+    assert_eq!(strings("factor"), vec!["NUM", "'(' NUM", "'(' '('"]);
+    assert_eq!(
+        strings("expr"),
+        vec!["NUM", "NUM '+'", "NUM '*'", "'(' NUM", "'(' '('"]
+    );
+}
@@ -0,0 +1,443 @@
+// Bison's packed tables, read back like yacc.c reads them, and C parsers
+// compiled and run with cc.
+use std::path::PathBuf;
+use std::process::Command;
+use yacc_parser::bison;
+use yacc_parser::bison::BisonTables;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::lr::Automaton;
+use yacc_parser::parser::Parser;
+use yacc_parser::symbols::SymbolId;
+use yacc_parser::symbols::SymbolTable;
+use yacc_parser::tables::Action;
+use yacc_parser::tables::Tables;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Decoded {
+    Shift(i32),
+    // By a Bison rule number
+    Reduce(i32),
+    Error,
+}
+
+// The action of a state on a token, by their Bison numbers
+fn action(tables: &BisonTables, state: usize, token: i32) -> Decoded {
+    let base = tables.yypact[state];
+    let at = base + token;
+    if base != tables.pact_ninf
+        && 0 <= at
+        && at as usize <= tables.last()
+        && tables.yycheck[at as usize] == token
+    {
+        return match tables.yytable[at as usize] {
+            value if value == tables.table_ninf => Decoded::Error,
+            value if value > 0 => Decoded::Shift(value),
+            value => Decoded::Reduce(-value),
+        };
+    }
+    match tables.yydefact[state] {
+        0 => Decoded::Error,
+        rule => Decoded::Reduce(rule),
+    }
+}
+
+fn goto(tables: &BisonTables, state: usize, nonterminal: i32) -> i32 {
+    let i = nonterminal as usize - tables.ntokens;
+    let at = tables.yypgoto[i] + state as i32;
+    if 0 <= at && at as usize <= tables.last() && tables.yycheck[at as usize] == state as i32 {
+        tables.yytable[at as usize]
+    } else {
+        tables.yydefgoto[i]
+    }
+}
+
+fn build(input: &str) -> (SymbolTable, Automaton, Tables, BisonTables) {
+    let grammar = Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap()
+        .desugar_mid_rule_actions();
+    let (symbols, diagnostics) = SymbolTable::new(&grammar);
+    assert!(diagnostics.is_empty());
+    let automaton = Automaton::lalr(&grammar, &symbols);
+    let tables = Tables::new(&automaton, &symbols);
+    let bison = BisonTables::new(&symbols, &automaton, &tables);
+    (symbols, automaton, tables, bison)
+}
+
+// Every action and goto of the tables is found in the packed ones, where
+// tokens without an action may take the default reduction instead
+fn assert_packed(input: &str) {
+    let (symbols, _, tables, bison) = build(input);
+    let tokens = symbols.terminals().count();
+    let number = |id: SymbolId| match id.0 {
+        0 | 1 => id.0 as i32,
+        i if i < tokens => i as i32 + 1,
+        i => i as i32 + 2,
+    };
+    for (state, row) in tables.action.iter().enumerate() {
+        for (id, _) in symbols.terminals() {
+            let decoded = action(&bison, state, number(id));
+            match row.get(&id) {
+                Some(Action::Shift(next)) => assert_eq!(decoded, Decoded::Shift(*next as i32)),
+                Some(Action::Accept) => {
+                    assert_eq!(decoded, Decoded::Shift(bison.final_state as i32))
+                }
+                Some(Action::Reduce(production)) => {
+                    assert_eq!(decoded, Decoded::Reduce(*production as i32 + 1))
+                }
+                Some(Action::Error) => assert_eq!(decoded, Decoded::Error),
+                None => assert!(matches!(decoded, Decoded::Error | Decoded::Reduce(_))),
+            }
+        }
+        for (&id, &next) in &tables.goto[state] {
+            assert_eq!(goto(&bison, state, number(id)), next as i32);
+        }
+    }
+}
+
+#[test]
+fn packed_tables() {
+    assert_packed(
+        "%token NUM
+%left '+' '-'
+%left '*'
+%right '^'
+%nonassoc '<'
+%%
+e: e '+' e | e '-' e | e '*' e | e '^' e | e '<' e | '(' e ')' | NUM ;
+%%
+",
+    );
+    assert_packed(
+        "%token IF ELSE X
+%%
+s: IF s | IF s ELSE s | X | error ';' | s ';' s ;
+%%
+",
+    );
+    assert_packed(
+        "%token A B C D E
+%%
+s: A x D | A y E | B x E | B y D | x C ;
+x: E ;
+y: E ;
+%%
+",
+    );
+}
+
+#[test]
+fn numbering() {
+    let (_, _, _, bison) = build(
+        "%token NUM
+%token LET \"let\"
+%%
+s: NUM '+' NUM | LET ;
+%%
+",
+    );
+    assert_eq!(
+        bison.yytname,
+        [
+            "$end",
+            "error",
+            "$undefined",
+            "NUM",
+            "\"let\"",
+            "'+'",
+            "$accept",
+            "s"
+        ]
+    );
+    assert_eq!(bison.yytranslate.len(), 260);
+    assert_eq!(bison.yytranslate[0], 0);
+    assert_eq!(bison.yytranslate[b'+' as usize], 5);
+    assert_eq!(bison.yytranslate[b'-' as usize], 2);
+    assert_eq!(bison.yytranslate[256], 1);
+    assert_eq!(&bison.yytranslate[258..], [3, 4]);
+    assert_eq!(bison.yyr1, [0, 6, 7, 7]);
+    assert_eq!(bison.yyr2, [0, 2, 3, 1]);
+}
+
+// Compiles the generated parser, returning what it prints
+fn run(name: &str, parser: &str) -> String {
+    run_with(name, parser, &[])
+}
+
+// Compiles the generated parser along with more files, the C ones among
+// them, returning what it prints
+fn run_with(name: &str, parser: &str, files: &[(&str, &str)]) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("parser.c"), parser).unwrap();
+    let mut sources = vec!["parser.c"];
+    for (file, contents) in files {
+        std::fs::write(dir.join(file), contents).unwrap();
+        if file.ends_with(".c") {
+            sources.push(file);
+        }
+    }
+    let output = Command::new(std::env::var("CC").unwrap_or("cc".to_string()))
+        .current_dir(&dir)
+        .args(["-std=c99", "-Wall", "-Werror", "-o", "parser"])
+        .args(sources)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let output = Command::new(dir.join("parser")).output().unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn generate(input: &str) -> String {
+    let grammar = Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap();
+    bison::generate(&grammar).unwrap()
+}
+
+#[test]
+fn calculator() {
+    let parser = generate(
+        r#"%{
+#include <stdio.h>
+#include <string.h>
+int yylex (void);
+void yyerror (const char *message);
+%}
+%union { long num; }
+%token <num> NUM
+%type <num> e
+%left '+' '-'
+%left '*'
+%%
+lines: | lines line ;
+line: e ';' { printf ("%ld\n", $1); }
+    | error ';' { printf ("recovered\n"); yyerrok; }
+    ;
+e: e '+' e { $$ = $1 + $3; }
+ | e '-' e { $$ = $1 - $3; }
+ | e '*' e { $$ = $1 * $3; }
+ | '(' e ')' { $$ = $2; }
+ | '-' { $<num>$ = -1; } NUM { $$ = $<num>2 * $3; }
+ | NUM
+ ;
+%%
+static const char *input = "1 + 2 * 3; (1 - 2) - 3; 1 + + 2; -4 * 2;";
+
+int yylex (void)
+{
+  while (*input == ' ')
+    input++;
+  if (*input >= '0' && *input <= '9')
+    {
+      yylval.num = 0;
+      while (*input >= '0' && *input <= '9')
+        yylval.num = yylval.num * 10 + *input++ - '0';
+      return NUM;
+    }
+  return *input ? *input++ : 0;
+}
+
+void yyerror (const char *message)
+{
+  printf ("%s\n", message);
+}
+
+int main (void)
+{
+  return yyparse ();
+}
+"#,
+    );
+    assert_eq!(
+        run("calculator", &parser),
+        "7\n-4\nsyntax error\nrecovered\n-8\n"
+    );
+}
+
+#[test]
+fn pure_parser() {
+    let parser = generate(
+        r#"%{
+#include <stdio.h>
+%}
+%pure-parser
+%locations
+%name-prefix="calc"
+%parse-param { int *sum }
+%parse-param { const char **input }
+%lex-param { const char **input }
+%union { int num; }
+%{
+int calclex (YYSTYPE *value, YYLTYPE *location, const char **input);
+void calcerror (YYLTYPE *location, int *sum, const char **input, const char *message);
+%}
+%token <num> NUM
+%%
+s: s NUM { *sum += $2; }
+ | NUM { *sum = $1; printf ("%d.%d\n", @1.first_line, @1.first_column); }
+ ;
+%%
+static const char *start;
+
+int calclex (YYSTYPE *value, YYLTYPE *location, const char **input)
+{
+  while (**input == ' ')
+    (*input)++;
+  location->first_line = 1;
+  location->first_column = 1 + (int) (*input - start);
+  if (!**input)
+    return 0;
+  if (**input < '0' || **input > '9')
+    return *(*input)++;
+  value->num = *(*input)++ - '0';
+  return NUM;
+}
+
+void calcerror (YYLTYPE *location, int *sum, const char **input, const char *message)
+{
+  (void) location;
+  (void) sum;
+  (void) input;
+  printf ("%s\n", message);
+}
+
+int main (void)
+{
+  const char *input = "  4 5 6";
+  int sum = 0;
+  int result;
+  start = input;
+  result = calcparse (&sum, &input);
+  printf ("%d %d\n", result, sum);
+  input = start = "4 x";
+  result = calcparse (&sum, &input);
+  printf ("%d\n", result);
+  return 0;
+}
+"#,
+    );
+    assert!(parser.contains("#define yyparse calcparse"));
+    assert_eq!(
+        run("pure_parser", &parser),
+        "1.3\n0 15\n1.1\nsyntax error\n1\n"
+    );
+}
+
+const SUM: &str = r#"%{
+#include <stdio.h>
+#include "y.tab.h"
+int sumlex (void);
+void sumerror (const char *message);
+%}
+%name-prefix="sum"
+%locations
+%union { int num; const char *op; }
+%token <num> NUM
+%token <op> PLUS "+"
+%type <num> s
+%%
+top: s { printf ("%d\n", $1); } ;
+s: s PLUS NUM { $$ = $1 + $3; } | NUM | s '-' NUM { $$ = $1 - $3; } ;
+%%
+void sumerror (const char *message)
+{
+  printf ("%s\n", message);
+}
+
+int main (void)
+{
+  return sumparse ();
+}
+"#;
+
+#[test]
+fn header() {
+    let grammar = Parser::new(SUM, Lexer::new(SUM)).parse_grammar().unwrap();
+    let header = bison::header(&grammar);
+    assert_eq!(
+        header,
+        "/* Token definitions of a parser generated by yacc-parser.  */
+
+#ifndef YY_SUM_Y_TAB_H_INCLUDED
+# define YY_SUM_Y_TAB_H_INCLUDED
+
+#ifndef YYTOKENTYPE
+# define YYTOKENTYPE
+enum yytokentype
+  {
+    NUM = 258,
+    PLUS = 259,
+  };
+#endif
+
+#if ! defined YYSTYPE && ! defined YYSTYPE_IS_DECLARED
+typedef union YYSTYPE
+{ int num; const char *op; } YYSTYPE;
+# define YYSTYPE_IS_DECLARED 1
+#endif
+
+#if ! defined YYLTYPE && ! defined YYLTYPE_IS_DECLARED
+typedef struct YYLTYPE
+{
+  int first_line;
+  int first_column;
+  int last_line;
+  int last_column;
+} YYLTYPE;
+# define YYLTYPE_IS_DECLARED 1
+#endif
+
+/* Token codes, for yacc compatibility.  */
+#define NUM 258
+#define PLUS 259
+
+extern YYSTYPE sumlval;
+extern YYLTYPE sumlloc;
+
+int sumparse (void);
+
+#endif
+"
+    );
+
+    // A lexer compiled on its own, with only the header
+    let lexer = r#"#include "y.tab.h"
+
+static const char *input = "1+2-4+10";
+
+int sumlex (void)
+{
+  sumlloc.first_column = sumlloc.last_column;
+  sumlloc.last_column++;
+  switch (*input)
+    {
+    case 0:
+      return 0;
+    case '+':
+      sumlval.op = input++;
+      return PLUS;
+    case '-':
+      return *input++;
+    default:
+      sumlval.num = *input++ - '0';
+      if (*input == '0')
+        sumlval.num = sumlval.num * 10 + *input++ - '0';
+      return NUM;
+    }
+}
+"#;
+    let parser = bison::generate(&grammar).unwrap();
+    assert_eq!(
+        run_with(
+            "header",
+            &parser,
+            &[("y.tab.h", &header), ("lexer.c", lexer)]
+        ),
+        "9\n"
+    );
+}
@@ -0,0 +1,285 @@
+// Checks of semantic value references in actions, and lints of symbols.
+use yacc_parser::check::check_actions;
+use yacc_parser::check::check_symbols;
+use yacc_parser::diagnostics::Severity;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::parser::Parser;
+
+// The severity, code, message and source text of each diagnostic
+fn check(input: &str) -> Vec<(Severity, Option<&'static str>, String, &str)> {
+    let grammar = Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap();
+    check_actions(&grammar)
+        .into_iter()
+        .map(|d| (d.severity, d.code, d.message, &input[d.span]))
+        .collect()
+}
+
+#[test]
+fn references_out_of_range() {
+    let input = "%%\na: b c { f($2, @3, $0, $-1); } | { g($1); } ;\n%%\n";
+    assert_eq!(
+        check(input),
+        vec![
+            (
+                Severity::Error,
+                Some("integer-out-of-range"),
+                "integer out of range: '@3'".to_string(),
+                "@3"
+            ),
+            (
+                Severity::Error,
+                Some("integer-out-of-range"),
+                "integer out of range: '$1'".to_string(),
+                "$1"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn overflowing_references() {
+    let input = "%%\na: b { f($99999999999, $-99999999999, $1); } c { $2147483647; } ;\n%%\n";
+    assert_eq!(
+        check(input),
+        vec![
+            (
+                Severity::Error,
+                Some("integer-out-of-range"),
+                "integer out of range: '$99999999999'".to_string(),
+                "$99999999999"
+            ),
+            (
+                Severity::Error,
+                Some("integer-out-of-range"),
+                "integer out of range: '$-99999999999'".to_string(),
+                "$-99999999999"
+            ),
+            (
+                Severity::Error,
+                Some("integer-out-of-range"),
+                "integer out of range: '$2147483647'".to_string(),
+                "$2147483647"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn named_references() {
+    let input = "%%\na: b c { f($b, $[c]); } | a a { $a; } | { $d; } ;\n%%\n";
+    assert_eq!(
+        check(input),
+        vec![
+            (
+                Severity::Error,
+                Some("ambiguous-reference"),
+                "ambiguous reference: '$a'".to_string(),
+                "$a"
+            ),
+            (
+                Severity::Error,
+                Some("invalid-reference"),
+                "invalid reference: '$d'".to_string(),
+                "$d"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn typed_values() {
+    let input = "%token <ival> NUM
+%type <ival> expr
+%%
+expr: NUM '+' NUM { $$ = $1; }
+    | NUM { f($1); }
+    | NUM
+    |
+    ;
+%%
+";
+    assert_eq!(
+        check(input),
+        vec![
+            (
+                Severity::Warning,
+                Some("unused-value"),
+                "unused value: $3".to_string(),
+                "NUM"
+            ),
+            (
+                Severity::Warning,
+                Some("unset-value"),
+                "unset value: $$".to_string(),
+                "{ f($1); }"
+            ),
+            (
+                Severity::Warning,
+                Some("empty-typed-rule"),
+                "empty rule for typed nonterminal, and no action".to_string(),
+                ""
+            ),
+        ]
+    );
+}
+
+#[test]
+fn mid_rule_actions() {
+    let input = "%token <ival> NUM
+%type <ival> expr
+%%
+expr: NUM { f($1, $2); } NUM { $$ = $2 + $3 + $4; }
+    | NUM { $$ = $1; } NUM
+    ;
+%%
+";
+    assert_eq!(
+        check(input),
+        vec![
+            (
+                Severity::Error,
+                Some("integer-out-of-range"),
+                "integer out of range: '$2'".to_string(),
+                "$2"
+            ),
+            (
+                Severity::Error,
+                Some("integer-out-of-range"),
+                "integer out of range: '$4'".to_string(),
+                "$4"
+            ),
+            // The value of the first mid-rule action is used but never set,
+            // the one of the second is set but never used
+            (
+                Severity::Warning,
+                Some("unset-value"),
+                "unset value: $$".to_string(),
+                "{ f($1, $2); }"
+            ),
+            (
+                Severity::Warning,
+                Some("unused-value"),
+                "unused value: $2".to_string(),
+                "{ $$ = $1; }"
+            ),
+            (
+                Severity::Warning,
+                Some("unused-value"),
+                "unused value: $3".to_string(),
+                "NUM"
+            ),
+        ]
+    );
+    // Mid-rule actions without a value of their own are not values
+    let input = "%%\nexpr: a { f(); } b { g($1, $3); } | a { $$ = 1; } { $$ = $2; } ;\n%%\n";
+    assert_eq!(check(input), vec![]);
+}
+
+#[test]
+fn default_action() {
+    // $$ = $1 uses the first value, but not the ones after it
+    let input = "%token <ival> NUM
+%type <ival> expr list
+%%
+expr: NUM | '(' expr ')' | expr '+' NUM ;
+list: expr | list ',' expr ;
+%%
+";
+    assert_eq!(
+        check(input),
+        vec![
+            (
+                Severity::Warning,
+                Some("unused-value"),
+                "unused value: $2".to_string(),
+                "expr"
+            ),
+            (
+                Severity::Warning,
+                Some("unused-value"),
+                "unused value: $3".to_string(),
+                "NUM"
+            ),
+            (
+                Severity::Warning,
+                Some("unused-value"),
+                "unused value: $3".to_string(),
+                "expr"
+            ),
+        ]
+    );
+}
+
+// The code, message and source text of each lint
+fn lint(input: &str) -> Vec<(Option<&'static str>, String, &str)> {
+    let grammar = Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap();
+    check_symbols(&grammar)
+        .into_iter()
+        .map(|d| (d.code, d.message, &input[d.span]))
+        .collect()
+}
+
+#[test]
+fn clean_grammar() {
+    let input = "%token NUM
+%left '+'
+%right UMINUS
+%type <ival> expr
+%%
+expr: expr '+' expr | '-' expr %prec UMINUS | NUM ;
+%%
+";
+    assert_eq!(lint(input), vec![]);
+}
+
+#[test]
+fn lints() {
+    let input = "%token NUM UNUSED
+%type <ival> expr nothing
+%%
+expr: NUM | loop | NUM | undefined ;
+loop: '(' loop ')' ;
+dead: NUM ;
+%%
+";
+    assert_eq!(
+        lint(input),
+        vec![
+            (
+                Some("undefined-symbol"),
+                "symbol undefined is used, but is not defined as a token and has no rules"
+                    .to_string(),
+                "undefined"
+            ),
+            (
+                Some("unused-token"),
+                "token UNUSED is never used".to_string(),
+                "UNUSED"
+            ),
+            (
+                Some("unproductive-nonterminal"),
+                "nonterminal loop derives no string of tokens".to_string(),
+                "loop"
+            ),
+            (
+                Some("unreachable-nonterminal"),
+                "nonterminal dead is unreachable".to_string(),
+                "dead"
+            ),
+            (
+                Some("duplicate-alternative"),
+                "duplicate alternative for expr".to_string(),
+                "NUM"
+            ),
+            (
+                Some("dangling-type"),
+                "%type given for nothing, which is not a symbol".to_string(),
+                "nothing"
+            ),
+        ]
+    );
+}
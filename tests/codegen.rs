@@ -0,0 +1,139 @@
+// Rust parsers generated from grammars, compiled and run with rustc.
+use std::path::PathBuf;
+use std::process::Command;
+use yacc_parser::codegen::generate;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::parser::Parser;
+
+fn generate_source(input: &str) -> Result<String, Vec<(String, String)>> {
+    let grammar = Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap();
+    generate(&grammar).map_err(|diagnostics| {
+        diagnostics
+            .into_iter()
+            .map(|d| (d.message, input[d.span].to_string()))
+            .collect()
+    })
+}
+
+// Compiles the generated parser with a main function, returning what it
+// prints
+fn run(name: &str, parser: &str, main: &str) -> String {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("parser.rs"), parser).unwrap();
+    std::fs::write(
+        dir.join("main.rs"),
+        format!(
+            "mod parser {{\n    include!(\"parser.rs\");\n}}\n\n{}",
+            main
+        ),
+    )
+    .unwrap();
+    let output = Command::new(std::env::var("RUSTC").unwrap_or("rustc".to_string()))
+        .current_dir(&dir)
+        .args([
+            "--edition",
+            "2021",
+            "-D",
+            "warnings",
+            "-o",
+            "main",
+            "main.rs",
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let output = Command::new(dir.join("main")).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+const CALCULATOR: &str = "%union { num: i64, list: Vec<i64>, name: String }
+%token <num> NUM
+%token <name> NAME
+%token LET \"let\"
+%type <num> expr
+%type <list> exprs
+%left '+' '-'
+%left '*'
+%%
+exprs: exprs ';' expr { $1.push($expr); $$ = $1; }
+     | expr { vec![$1] }
+     ;
+expr: expr '+' expr { $$ = $1 + $3; }
+    | expr '-' expr { $1 - $3 }
+    | expr '*' expr { $$ = $1 * $3; }
+    | '(' expr ')' { $$ = $2; }
+    | NUM
+    | LET NAME { $<num>$ = $2.len() as i64; } '=' expr { $5 * $<num>3 }
+    ;
+%%
+";
+
+#[test]
+fn calculator() {
+    let parser = generate_source(CALCULATOR).unwrap();
+    let output = run(
+        "calculator",
+        &parser,
+        "use parser::parse;
+use parser::Token::*;
+
+fn main() {
+    let tokens = vec![
+        NUM(1), Char('+'), NUM(2), Char('*'), NUM(3), Char(';'),
+        Char('('), NUM(1), Char('-'), NUM(2), Char(')'), Char('-'), NUM(3), Char(';'),
+        LET, NAME(\"abc\".to_string()), Char('='), NUM(2),
+    ];
+    println!(\"{:?}\", parse(tokens).unwrap());
+    for tokens in [vec![NUM(1), NUM(2)], vec![NUM(1), Char('+')], vec![Char('!')]] {
+        println!(\"{}\", parse(tokens).unwrap_err());
+    }
+}
+",
+    );
+    assert_eq!(
+        output,
+        "[7, -4, 6]
+syntax error, unexpected NUM, expecting $end or '+' or '-' or '*' or ';' or ')'
+syntax error, unexpected end of input, expecting NUM or \"let\" or '('
+syntax error, unexpected '!', expecting NUM or \"let\" or '('
+"
+    );
+}
+
+#[test]
+fn unsupported() {
+    let errors = generate_source(
+        "%token <i64> NUM
+%type <i64> e
+%%
+e: NUM { $$ = @1; } | '-' | e \"+\" e ;
+%%
+",
+    )
+    .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            (
+                "token \"+\" has no name to use in the Token enum".to_string(),
+                "\"+\"".to_string()
+            ),
+            (
+                "@1: locations are not supported in Rust parsers".to_string(),
+                "@1".to_string()
+            ),
+            (
+                "type clash on default action: i64 != ()".to_string(),
+                "'-'".to_string()
+            ),
+        ]
+    );
+}
@@ -0,0 +1,124 @@
+// Counterexamples explaining conflicts.
+use yacc_parser::counterexample::Explainer;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::lr::Automaton;
+use yacc_parser::parser::Parser;
+use yacc_parser::symbols::SymbolTable;
+use yacc_parser::tables::Tables;
+
+// The example and derivation of each side, and whether they unify, for
+// each conflict
+fn explain(input: &str) -> Vec<(Vec<String>, bool)> {
+    let grammar = Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap()
+        .desugar_mid_rule_actions();
+    let (symbols, _) = SymbolTable::new(&grammar);
+    let automaton = Automaton::lalr(&grammar, &symbols);
+    let tables = Tables::new(&automaton, &symbols);
+    let explainer = Explainer::new(&grammar, &symbols, &automaton);
+    tables
+        .conflicts
+        .iter()
+        .map(|conflict| {
+            let counterexample = explainer.explain(conflict).unwrap();
+            let mut lines = Vec::new();
+            for derivation in &counterexample.derivations {
+                lines.push(explainer.example(derivation));
+                lines.push(explainer.render(derivation));
+            }
+            (lines, counterexample.unifying)
+        })
+        .collect()
+}
+
+#[test]
+fn ambiguous_expressions() {
+    let input = "%token NUM\n%%\ne: e '+' e | NUM ;\n%%\n";
+    assert_eq!(
+        explain(input),
+        vec![(
+            vec![
+                "e '+' e • '+' e".to_string(),
+                "e → e '+' [ e → e • '+' e ]".to_string(),
+                "e '+' e • '+' e".to_string(),
+                "e → [ e → e '+' e • ] '+' e".to_string(),
+            ],
+            true
+        )]
+    );
+}
+
+#[test]
+fn dangling_else() {
+    let input = "%token IF ELSE X\n%%\ns: IF s | IF s ELSE s | X ;\n%%\n";
+    assert_eq!(
+        explain(input),
+        vec![(
+            vec![
+                "IF IF s • ELSE s".to_string(),
+                "s → IF [ s → IF s • ELSE s ]".to_string(),
+                "IF IF s • ELSE s".to_string(),
+                "s → IF [ s → IF s • ] ELSE s".to_string(),
+            ],
+            true
+        )]
+    );
+}
+
+#[test]
+fn needs_more_lookahead() {
+    let input = "%token A B\n%%\ns: x A B | y A A ;\nx: A ;\ny: A ;\n%%\n";
+    assert_eq!(
+        explain(input),
+        vec![(
+            vec![
+                "A • A B".to_string(),
+                "s → [ x → A • ] A B".to_string(),
+                "A • A A".to_string(),
+                "s → [ y → A • ] A A".to_string(),
+            ],
+            false
+        )]
+    );
+}
+
+#[test]
+fn lalr_merge() {
+    let input = "%%
+s: 'a' e 'c' | 'a' f 'd' | 'b' f 'c' | 'b' e 'd' ;
+e: 'e' ;
+f: 'e' ;
+%%
+";
+    let explained = explain(input);
+    assert_eq!(
+        explained[0],
+        (
+            vec![
+                "'a' 'e' • 'c'".to_string(),
+                "s → 'a' [ e → 'e' • ] 'c'".to_string(),
+                "'b' 'e' • 'c'".to_string(),
+                "s → 'b' [ f → 'e' • ] 'c'".to_string(),
+            ],
+            false
+        )
+    );
+}
+
+#[test]
+fn lookahead_through_empty_rules() {
+    let input = "%%\ns: a o t | b 'x' ;\nt: 'x' 'y' ;\no: ;\na: ;\nb: ;\n%%\n";
+    assert_eq!(
+        explain(input),
+        vec![(
+            vec![
+                "• 'x' 'y'".to_string(),
+                "s → [ a → • ] [ o → ε ] [ t → 'x' 'y' ]".to_string(),
+                "• 'x'".to_string(),
+                "s → [ b → • ] 'x'".to_string(),
+            ],
+            false
+        )]
+    );
+}
@@ -0,0 +1,127 @@
+// The concrete syntax tree reproduces its input and parses to the same
+// grammar as the parser.
+use proptest::prelude::*;
+use yacc_parser::cst::NodeKind;
+use yacc_parser::cst::SyntaxElement;
+use yacc_parser::cst::SyntaxTree;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::parser::Parser;
+use yacc_parser::token::Token;
+
+fn check(input: &str) {
+    let tree = SyntaxTree::parse(input).unwrap_or_else(|err| panic!("{}\n{}", err, input));
+    assert_eq!(tree.to_string(), input);
+    let grammar = Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap();
+    assert_eq!(tree.grammar().unwrap(), grammar);
+}
+
+#[test]
+fn comments() {
+    check(
+        "/* header */
+%token A // trailing
+// before the rules
+%%
+// before a rule
+a /* name */ : A /* element */ { /* in code */ } // after
+  | /* empty */
+  ;
+%%
+",
+    );
+}
+
+#[test]
+fn tabs_and_blank_lines() {
+    check("%token\tA\tB\n\n%%\n\ta\t:\tA\n\t|\tB\t;\n\n\n%%");
+}
+
+#[test]
+fn empty_alternatives() {
+    check("%%\na: | A | ;\nb: ;\nc:\n  |\n  ;\n%%\n");
+}
+
+#[test]
+fn prologue_and_union() {
+    check(
+        "%{
+#include <stdio.h>
+/* a comment */ %}
+%union {
+    int num; /* } */
+    char *name;
+}
+%{ int yylex(void); %}
+%token <num> NUM
+%%
+a: NUM { printf(\"%d }\", $1); } ;
+%%
+",
+    );
+}
+
+#[test]
+fn trivia_after_epilogue() {
+    check("%%\na: ;\n%%");
+    check("%%\na: ;\n%%\n\n/* c */\n  \n");
+    check("%%\na: ;\n%%\nint main(void) { return 0; } // end\n\t");
+}
+
+#[test]
+fn trivia_is_attached_to_tokens() {
+    let input = "%token A // first\n// second\n%%\na: A ;\n%%\n";
+    let tree = SyntaxTree::parse(input).unwrap();
+    let SyntaxElement::Node(directive) = &tree.root.children[0] else {
+        panic!("expected a directive node");
+    };
+    assert_eq!(directive.kind, NodeKind::Directive);
+    let tokens = tree.root.tokens();
+    let text = |trivia: &[yacc_parser::cst::Trivia]| {
+        trivia
+            .iter()
+            .map(|t| tree.text(t.span.clone()))
+            .collect::<String>()
+    };
+    // Trivia up to the end of the line trails a token, the rest, from the
+    // newline on, leads the next one
+    assert_eq!(tokens[1].kind, Token::Ident);
+    assert_eq!(text(&tokens[1].trailing), " // first");
+    assert_eq!(tokens[2].kind, Token::PercentPercent);
+    assert_eq!(text(&tokens[2].leading), "\n// second\n");
+}
+
+fn trivia() -> impl Strategy<Value = String> {
+    prop::collection::vec(
+        prop_oneof![
+            Just(" ".to_string()),
+            Just("\t".to_string()),
+            Just("\n".to_string()),
+            Just("/* c */".to_string()),
+            Just("// c\n".to_string()),
+        ],
+        0..3,
+    )
+    .prop_map(|trivia| trivia.concat())
+}
+
+proptest! {
+    // Any whitespace and comments between the tokens are kept
+    #[test]
+    fn round_trip(trivia in prop::collection::vec(trivia(), 24)) {
+        let tokens = [
+            "%token", "<num>", "NUM", "\"+\"", "%left", "'+'", "%%", "e", ":", "e", "'+'",
+            "e", "{ $$ = $1 + $3; }", "|", "NUM", "|", ";", "f", ":", "e", "%prec", "NUM",
+            ";",
+        ];
+        let mut input = trivia[0].clone();
+        for (token, trivia) in tokens.iter().zip(&trivia[1..]) {
+            input.push_str(token);
+            // Tokens must stay apart
+            input.push_str(if trivia.is_empty() { " " } else { trivia });
+        }
+        input.push_str("%%\n");
+        check(&input);
+    }
+}
@@ -0,0 +1,139 @@
+// Line and column lookup, and rendering of diagnostics as source snippets.
+use yacc_parser::diagnostics::Diagnostic;
+use yacc_parser::diagnostics::LineIndex;
+use yacc_parser::diagnostics::Renderer;
+
+const INPUT: &str = "%token A
+%%
+expr: expr '+'
+\t| A ;
+long: A
+  A
+  A
+  A ;
+%%
+";
+
+#[test]
+fn line_index() {
+    let input = "ab\r\né\tc\n\nx";
+    let index = LineIndex::new(input);
+    assert_eq!(index.line_count(), 4);
+    assert_eq!(
+        (0..=input.len()).map(|i| index.line(i)).collect::<Vec<_>>(),
+        vec![0, 0, 0, 0, 1, 1, 1, 1, 1, 2, 3, 3]
+    );
+    // Line spans exclude the newline, and a carriage return before it
+    assert_eq!(index.line_span(input, 0), 0..2);
+    assert_eq!(index.line_span(input, 1), 4..8);
+    assert_eq!(index.line_span(input, 2), 9..9);
+    assert_eq!(index.line_span(input, 3), 10..11);
+    // Columns count characters, not bytes
+    assert_eq!(index.line_col(input, 0), (1, 1));
+    assert_eq!(index.line_col(input, 6), (2, 2));
+    assert_eq!(index.line_col(input, 7), (2, 3));
+    assert_eq!(index.line_col(input, 11), (4, 2));
+}
+
+fn diagnostic() -> Diagnostic {
+    Diagnostic::error("type clash".to_string(), 18..22)
+        .with_code("clash")
+        .with_primary_label("used here".to_string())
+        .with_secondary_label(12..16, "defined here".to_string())
+        .with_note("the types differ".to_string())
+        .with_help("add a cast".to_string())
+}
+
+#[test]
+fn plain() {
+    assert_eq!(
+        Renderer::new("g.y", INPUT).render(&diagnostic()),
+        "error[clash]: type clash
+ --> g.y:3:7
+  |
+3 | expr: expr '+'
+  | ---- defined here
+  |       ^^^^ used here
+  |
+  = note: the types differ
+  = help: add a cast
+"
+    );
+}
+
+#[test]
+fn color() {
+    assert_eq!(
+        Renderer::new("g.y", INPUT)
+            .with_color(true)
+            .render(&diagnostic()),
+        "\x1b[1;31merror[clash]\x1b[0m\x1b[1m: type clash\x1b[0m
+\x1b[1;34m -->\x1b[0m g.y:3:7
+\x1b[1;34m  |\x1b[0m
+\x1b[1;34m3 |\x1b[0m expr: expr '+'
+\x1b[1;34m  |\x1b[0m \x1b[1;34m---- defined here\x1b[0m
+\x1b[1;34m  |\x1b[0m       \x1b[1;31m^^^^ used here\x1b[0m
+\x1b[1;34m  |\x1b[0m
+\x1b[1;34m  =\x1b[0m\x1b[1m note\x1b[0m: the types differ
+\x1b[1;34m  =\x1b[0m\x1b[1m help\x1b[0m: add a cast
+"
+    );
+}
+
+#[test]
+fn tabs() {
+    let diagnostic =
+        Diagnostic::warning("tab".to_string(), 30..31).with_primary_label("after".to_string());
+    assert_eq!(
+        Renderer::new("g.y", INPUT).render(&diagnostic),
+        "warning: tab
+ --> g.y:4:4
+  |
+4 |     | A ;
+  |       ^ after
+"
+    );
+}
+
+#[test]
+fn multi_line() {
+    // Long spans show their first two and their last line
+    let diagnostic = Diagnostic::error("long".to_string(), 34..56)
+        .with_primary_label("this rule".to_string())
+        .with_secondary_label(0..8, "declared".to_string());
+    assert_eq!(
+        Renderer::new("g.y", INPUT).render(&diagnostic),
+        "error: long
+ --> g.y:5:1
+  |
+1 | %token A
+  | -------- declared
+...
+5 | long: A
+  | ^^^^^^^
+6 |   A
+  | ^^^
+...
+8 |   A ;
+  | ^^^^^ this rule
+"
+    );
+}
+
+#[test]
+fn end_of_input() {
+    let renderer = Renderer::new("g.y", INPUT);
+    let expected = "error: eof
+  --> g.y:10:1
+   |
+10 |
+   | ^ here
+";
+    let at_end = Diagnostic::error("eof".to_string(), INPUT.len()..INPUT.len())
+        .with_primary_label("here".to_string());
+    assert_eq!(renderer.render(&at_end), expected);
+    // Spans past the end are clamped to it
+    let past_end = Diagnostic::error("eof".to_string(), INPUT.len() + 5..INPUT.len() + 9)
+        .with_primary_label("here".to_string());
+    assert_eq!(renderer.render(&past_end), expected);
+}
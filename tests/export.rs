@@ -0,0 +1,248 @@
+// Grammars exported as JSON with the serde feature, and as EBNF.
+#[cfg(feature = "serde")]
+use yacc_parser::export::from_json;
+use yacc_parser::export::to_ebnf;
+#[cfg(feature = "serde")]
+use yacc_parser::export::to_json;
+use yacc_parser::export::Dialect;
+use yacc_parser::grammar::Grammar;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::parser::Parser;
+
+fn parse(input: &str) -> Grammar {
+    Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap()
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn schema() {
+    let json = to_json(&parse(
+        "%token <num> NUM \"number\"\n%%\ne: e '+' NUM { $$ = $1 + $3; } ;\n%%\n",
+    ));
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "version": 1,
+            "grammar": {
+                "directives": [{
+                    "data": {
+                        "kind": "Token",
+                        "token_name": { "data": "<num>", "span": { "start": 7, "end": 12 } },
+                        "rule_names": [{
+                            "name": { "data": "NUM", "span": { "start": 13, "end": 16 } },
+                            "alias": { "data": "\"number\"", "span": { "start": 17, "end": 25 } },
+                        }],
+                    },
+                    "span": { "start": 0, "end": 25 },
+                }],
+                "rules": [{
+                    "name": { "data": "e", "span": { "start": 29, "end": 30 } },
+                    "alternatives": [{
+                        "elements": [
+                            { "kind": "Symbol", "data": "e", "span": { "start": 32, "end": 33 } },
+                            {
+                                "kind": "Char",
+                                "text": { "data": "'+'", "span": { "start": 34, "end": 37 } },
+                                "value": "+",
+                            },
+                            { "kind": "Symbol", "data": "NUM", "span": { "start": 38, "end": 41 } },
+                        ],
+                        "precedence": null,
+                        "action": {
+                            "code": { "data": "{ $$ = $1 + $3; }", "span": { "start": 42, "end": 59 } },
+                            "fragments": [
+                                { "kind": "Text", "data": "{ ", "span": { "start": 42, "end": 44 } },
+                                {
+                                    "kind": "Reference",
+                                    "data": {
+                                        "kind": "Value",
+                                        "type_tag": null,
+                                        "target": { "kind": "Result" },
+                                    },
+                                    "span": { "start": 44, "end": 46 },
+                                },
+                                { "kind": "Text", "data": " = ", "span": { "start": 46, "end": 49 } },
+                                {
+                                    "kind": "Reference",
+                                    "data": {
+                                        "kind": "Value",
+                                        "type_tag": null,
+                                        "target": { "kind": "Index", "value": 1 },
+                                    },
+                                    "span": { "start": 49, "end": 51 },
+                                },
+                                { "kind": "Text", "data": " + ", "span": { "start": 51, "end": 54 } },
+                                {
+                                    "kind": "Reference",
+                                    "data": {
+                                        "kind": "Value",
+                                        "type_tag": null,
+                                        "target": { "kind": "Index", "value": 3 },
+                                    },
+                                    "span": { "start": 54, "end": 56 },
+                                },
+                                { "kind": "Text", "data": "; }", "span": { "start": 56, "end": 59 } },
+                            ],
+                        },
+                        "span": { "start": 32, "end": 59 },
+                    }],
+                    "span": { "start": 29, "end": 61 },
+                }],
+                "prologues": [],
+                "epilogue": { "data": "\n", "span": { "start": 62, "end": 65 } },
+            },
+        })
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn roundtrip() {
+    let input = "%{
+#include <stdio.h>
+%}
+%union { int num; }
+%pure-parser
+%locations
+%expect 1
+%token <num> NUM
+%left '+' '-'
+%type <num> expr
+%%
+expr: expr '+' expr { $$ = $1 + $3; }
+    | expr '-' expr %prec NUM { $<num>$ = $[expr]; }
+    | NUM
+    | { @$ = @0; } expr
+    ;
+%%
+int main(void) { return 0; }
+";
+    let grammar = parse(input);
+    let back = from_json(&to_json(&grammar)).unwrap();
+    assert_eq!(back, grammar);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn version() {
+    assert_eq!(
+        from_json("{ \"version\": 2, \"grammar\": null }"),
+        Err("unsupported schema version 2, expected 1".to_string())
+    );
+    assert!(from_json("{ \"grammar\": {} }").is_err());
+}
+
+const LISTS: &str = "%token SELECT \"SELECT\" FROM \"FROM\" WHERE \"WHERE\" IDENT NUM
+%left '+'
+%%
+stmts: stmts stmt ';' | stmt ';' ;
+stmt: SELECT columns FROM tables opt_where { run(); } ;
+columns: columns ',' column | column ;
+column: expr | expr \"AS\" IDENT %prec NUM | '*' ;
+tables: IDENT ',' tables | IDENT ;
+opt_where: | WHERE expr ;
+args: | args { mid(); } expr ;
+items: | items ',' IDENT | IDENT ;
+prefixed: '-' prefixed | '+' prefixed | NUM ;
+expr: expr '+' expr | NUM | IDENT | '(' expr ')' ;
+nothing: ;
+column: NUM NUM ;
+%%
+";
+
+#[test]
+fn w3c() {
+    assert_eq!(
+        to_ebnf(&parse(LISTS), Dialect::W3c),
+        "stmts ::= (stmt ';')+
+
+stmt ::= 'SELECT' columns 'FROM' tables opt_where
+
+columns ::= column (',' column)*
+
+column ::= expr
+         | expr 'AS' IDENT
+         | '*'
+         | NUM NUM
+
+tables ::= IDENT (',' IDENT)*
+
+opt_where ::= ('WHERE' expr)?
+
+args ::= expr*
+
+items ::= IDENT? (',' IDENT)*
+
+prefixed ::= ('-' | '+')* NUM
+
+expr ::= (NUM | IDENT | '(' expr ')') ('+' expr)*
+
+nothing ::= /* empty */
+"
+    );
+}
+
+#[test]
+fn iso() {
+    assert_eq!(
+        to_ebnf(&parse(LISTS), Dialect::Iso),
+        "stmts = stmt, ';', { stmt, ';' } ;
+
+stmt = 'SELECT', columns, 'FROM', tables, opt_where ;
+
+columns = column, { ',', column } ;
+
+column = expr
+       | expr, 'AS', IDENT
+       | '*'
+       | NUM, NUM ;
+
+tables = IDENT, { ',', IDENT } ;
+
+opt_where = [ 'WHERE', expr ] ;
+
+args = { expr } ;
+
+items = [ IDENT ], { ',', IDENT } ;
+
+prefixed = { '-' | '+' }, NUM ;
+
+expr = (NUM | IDENT | '(', expr, ')'), { '+', expr } ;
+
+nothing = (* empty *) ;
+"
+    );
+}
+
+#[test]
+fn lists() {
+    let ebnf = |rules: &str| to_ebnf(&parse(&format!("%%\n{}\n%%\n", rules)), Dialect::W3c);
+    // Right recursion
+    assert_eq!(ebnf("a: 'x' a | 'x' ;"), "a ::= 'x'+\n");
+    assert_eq!(ebnf("a: 'x' a | ;"), "a ::= 'x'*\n");
+    assert_eq!(ebnf("a: 'x' ',' a | 'x' ;"), "a ::= 'x' (',' 'x')*\n");
+    // The optional list may end with a separator
+    assert_eq!(ebnf("a: 'x' ',' a | 'x' | ;"), "a ::= ('x' ',')* 'x'?\n");
+    // Without a base the rule matches nothing, and stays recursive
+    assert_eq!(ebnf("a: a 'x' ;"), "a ::= a 'x'\n");
+    assert_eq!(ebnf("a: a 'x' | a 'y' | ;"), "a ::= ('x' | 'y')*\n");
+    assert_eq!(ebnf("a: a 'x' | 'y' | ;"), "a ::= 'y'? 'x'*\n");
+}
+
+#[test]
+fn literals() {
+    let ebnf = |dialect| {
+        to_ebnf(
+            &parse("%%\na: '\\'' '\\n' \"it's \\\"x\\\"\" ;\n%%\n"),
+            dialect,
+        )
+    };
+    assert_eq!(ebnf(Dialect::W3c), "a ::= \"'\" #xA \"it's \" '\"x\"'\n");
+    assert_eq!(
+        ebnf(Dialect::Iso),
+        "a = \"'\", ? U+000A ?, \"it's \", '\"x\"' ;\n"
+    );
+}
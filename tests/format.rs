@@ -0,0 +1,129 @@
+// Layouts of the formatter, comments it keeps, and `yacc-parser fmt`.
+use std::path::PathBuf;
+use std::process::Command;
+use yacc_parser::format::format_source;
+use yacc_parser::format::FirstAlternative;
+use yacc_parser::format::FormatOptions;
+
+fn layout(input: &str, first_alternative: FirstAlternative) -> String {
+    let options = FormatOptions {
+        first_alternative,
+        ..FormatOptions::default()
+    };
+    format_source(input, &options).unwrap()
+}
+
+#[test]
+fn bar() {
+    assert_eq!(
+        layout(
+            "%%\ne: e '+' e { $$ = $1 + $3; } | NUM ;\nopt: | A | { none(); } ;\n%%\n",
+            FirstAlternative::Bar
+        ),
+        "%%
+e:
+      e '+' e { $$ = $1 + $3; }
+    | NUM
+    ;
+
+opt:
+    | A
+    | { none(); }
+    ;
+
+%%
+"
+    );
+}
+
+const COMMENTED: &str = "/* calculator */
+%token NUM // a number
+%left '+'
+%%
+// the start symbol
+e /* expression */ : e '+' e /* sum */ { $$ = $1 + $3; } // add
+  // a plain number
+  | NUM ;
+%%
+";
+
+const FORMATTED: &str = "/* calculator */
+%token NUM // a number
+%left '+'
+%%
+// the start symbol
+e /* expression */
+    : e '+' e /* sum */ { $$ = $1 + $3; } // add
+    // a plain number
+    | NUM
+    ;
+
+%%
+";
+
+#[test]
+fn comments() {
+    let options = FormatOptions::default();
+    assert_eq!(format_source(COMMENTED, &options).unwrap(), FORMATTED);
+    assert_eq!(format_source(FORMATTED, &options).unwrap(), FORMATTED);
+    let options = FormatOptions {
+        preserve_comments: false,
+        ..FormatOptions::default()
+    };
+    assert_eq!(
+        format_source(COMMENTED, &options).unwrap(),
+        "%token NUM
+%left '+'
+%%
+e
+    : e '+' e { $$ = $1 + $3; }
+    | NUM
+    ;
+
+%%
+"
+    );
+}
+
+// Runs `yacc-parser fmt` on a copy of the input, returning its exit code,
+// what it printed and the file afterwards
+fn fmt(name: &str, input: &str, args: &[&str]) -> (i32, String, String) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("fmt");
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join(name);
+    std::fs::write(&file, input).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_yacc-parser"))
+        .arg("fmt")
+        .args(args)
+        .arg(&file)
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .replace(file.to_str().unwrap(), name),
+        std::fs::read_to_string(&file).unwrap(),
+    )
+}
+
+#[test]
+fn check() {
+    assert_eq!(
+        fmt("unformatted.y", COMMENTED, &["--check"]),
+        (
+            1,
+            "unformatted.y is not formatted\n".to_string(),
+            COMMENTED.to_string()
+        )
+    );
+    assert_eq!(
+        fmt("formatted.y", FORMATTED, &["--check"]),
+        (0, String::new(), FORMATTED.to_string())
+    );
+    // Without --check the file is rewritten
+    assert_eq!(
+        fmt("in_place.y", COMMENTED, &[]),
+        (0, String::new(), FORMATTED.to_string())
+    );
+}
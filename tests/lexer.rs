@@ -0,0 +1,38 @@
+// Code blocks, which end at the first '}' outside of C literals and
+// comments.
+use yacc_parser::lexer::Lexer;
+use yacc_parser::token::Token;
+
+// The kind and text of each token
+fn tokens(input: &str) -> Vec<(Token, &str)> {
+    Lexer::new(input)
+        .map(|t| (t.data, &input[t.span]))
+        .collect()
+}
+
+#[test]
+fn braces_in_literals_and_comments() {
+    let code = r#"{ f("}", '}', '\'', "\"}"); /* } */ // }
+}"#;
+    assert_eq!(
+        tokens(&format!("{} ;", code)),
+        vec![(Token::Code, code), (Token::SemiColon, ";")]
+    );
+}
+
+#[test]
+fn lifetimes() {
+    // A quote before a name which is not closed starts a lifetime, not a
+    // character literal
+    for code in [
+        r#"{ let s: &'static str = ""; }"#,
+        "{ fn f<'a>(s: &'a str) -> &'a str { s } }",
+        "{ 'outer: loop { break 'outer; } }",
+        "{ let c = 'x'; let d = '_'; }",
+    ] {
+        assert_eq!(
+            tokens(&format!("{} ;", code)),
+            vec![(Token::Code, code), (Token::SemiColon, ";")]
+        );
+    }
+}
@@ -0,0 +1,365 @@
+// LALR(1), canonical and minimal LR(1) automata and their parse tables.
+use yacc_parser::diagnostics::Severity;
+use yacc_parser::grammar::Directive;
+use yacc_parser::grammar::Grammar;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::lr::Automaton;
+use yacc_parser::lr::Mode;
+use yacc_parser::parser::Parser;
+use yacc_parser::symbols::SymbolTable;
+use yacc_parser::tables::check_conflicts;
+use yacc_parser::tables::Action;
+use yacc_parser::tables::ConflictKind;
+use yacc_parser::tables::Tables;
+
+struct Built {
+    grammar: Grammar,
+    symbols: SymbolTable,
+    automaton: Automaton,
+    tables: Tables,
+}
+
+fn build(input: &str) -> Built {
+    build_with(input, Mode::Lalr)
+}
+
+fn build_with(input: &str, mode: Mode) -> Built {
+    let grammar = Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap()
+        .desugar_mid_rule_actions();
+    let (symbols, diagnostics) = SymbolTable::new(&grammar);
+    assert!(diagnostics.is_empty());
+    let automaton = Automaton::build(&grammar, &symbols, mode);
+    let tables = Tables::new(&automaton, &symbols);
+    Built {
+        grammar,
+        symbols,
+        automaton,
+        tables,
+    }
+}
+
+// Runs the tables over the tokens, returning the productions reduced by, or
+// None on a syntax error
+fn parse(built: &Built, tokens: &str) -> Option<Vec<usize>> {
+    let mut input = tokens
+        .split_whitespace()
+        .map(|name| built.symbols.get(name).unwrap())
+        .chain([SymbolTable::END])
+        .peekable();
+    let mut stack = vec![0];
+    let mut reductions = Vec::new();
+    loop {
+        let state = *stack.last().unwrap();
+        let token = *input.peek().unwrap();
+        match built.tables.action[state].get(&token)? {
+            Action::Shift(next) => {
+                stack.push(*next);
+                input.next();
+            }
+            Action::Reduce(production) => {
+                reductions.push(*production);
+                let production = &built.automaton.productions[*production];
+                stack.truncate(stack.len() - production.rhs.len());
+                let state = *stack.last().unwrap();
+                stack.push(built.tables.goto[state][&production.lhs]);
+            }
+            Action::Accept => return Some(reductions),
+            Action::Error => return None,
+        }
+    }
+}
+
+#[test]
+fn precedence_resolves_conflicts() {
+    let built = build(
+        "%token NUM
+%left '+' '-'
+%left '*'
+%right '^'
+%nonassoc '<'
+%%
+e: e '+' e | e '-' e | e '*' e | e '^' e | e '<' e | NUM ;
+%%
+",
+    );
+    assert_eq!(built.tables.conflicts, vec![]);
+    assert_eq!(
+        parse(&built, "NUM '+' NUM '*' NUM '-' NUM"),
+        Some(vec![6, 6, 6, 3, 1, 6, 2])
+    );
+    assert_eq!(
+        parse(&built, "NUM '^' NUM '^' NUM"),
+        Some(vec![6, 6, 6, 4, 4])
+    );
+    assert!(parse(&built, "NUM '<' NUM '<' NUM").is_none());
+    assert!(parse(&built, "NUM '+' '*' NUM").is_none());
+}
+
+#[test]
+fn lalr_but_not_slr() {
+    let built = build(
+        "%token ID
+%%
+s: l '=' r | r ;
+l: '*' r | ID ;
+r: l ;
+%%
+",
+    );
+    assert_eq!(built.tables.conflicts, vec![]);
+    assert_eq!(parse(&built, "'*' ID '=' ID"), Some(vec![4, 5, 3, 4, 5, 1]));
+}
+
+#[test]
+fn dangling_else() {
+    let input = "%token IF ELSE X
+%expect 1
+%%
+s: IF s | IF s ELSE s | X ;
+%%
+";
+    let built = build(input);
+    assert_eq!(built.tables.count(ConflictKind::ShiftReduce), 1);
+    let diagnostics = check_conflicts(
+        &built.grammar,
+        &built.symbols,
+        &built.automaton,
+        &built.tables,
+    );
+    assert!(diagnostics.is_empty());
+
+    let input = input.replace("%expect 1", "%expect 0");
+    let built = build(&input);
+    let diagnostics = check_conflicts(
+        &built.grammar,
+        &built.symbols,
+        &built.automaton,
+        &built.tables,
+    )
+    .into_iter()
+    .map(|d| (d.severity, d.message, &input[d.span]))
+    .collect::<Vec<_>>();
+    assert_eq!(
+        diagnostics,
+        vec![
+            (
+                Severity::Error,
+                "shift/reduce conflicts: 1 found, 0 expected".to_string(),
+                "%expect 0"
+            ),
+            (
+                Severity::Warning,
+                "shift/reduce conflict on token ELSE".to_string(),
+                "IF s"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn reduce_reduce() {
+    let input = "%token X Y
+%%
+s: a | b ;
+a: X ;
+b: Y { mid(); } X | X ;
+%%
+";
+    let built = build(input);
+    assert_eq!(built.tables.count(ConflictKind::ShiftReduce), 0);
+    assert_eq!(built.tables.count(ConflictKind::ReduceReduce), 1);
+    let diagnostics = check_conflicts(
+        &built.grammar,
+        &built.symbols,
+        &built.automaton,
+        &built.tables,
+    )
+    .into_iter()
+    .map(|d| (d.severity, d.message, &input[d.span]))
+    .collect::<Vec<_>>();
+    assert_eq!(
+        diagnostics,
+        vec![(
+            Severity::Warning,
+            "reduce/reduce conflict on token $end".to_string(),
+            "X"
+        )]
+    );
+}
+
+#[test]
+fn expect_rr() {
+    let grammar = "%token X
+%%
+s: a | b | X X ;
+a: X ;
+b: X ;
+%%
+";
+    let errors = |directives: &str| {
+        let input = format!("{}\n{}", directives, grammar);
+        let built = build(&input);
+        check_conflicts(
+            &built.grammar,
+            &built.symbols,
+            &built.automaton,
+            &built.tables,
+        )
+        .into_iter()
+        .filter(|d| d.severity == Severity::Error)
+        .map(|d| (d.message, input[d.span].to_string()))
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(errors("%expect-rr 1"), vec![]);
+    assert_eq!(errors("%expect 0\n%expect-rr 1"), vec![]);
+    assert_eq!(
+        errors("%expect-rr 2"),
+        vec![(
+            "reduce/reduce conflicts: 1 found, 2 expected".to_string(),
+            "%expect-rr 2".to_string()
+        )]
+    );
+    // %expect alone expects no reduce/reduce conflicts
+    assert_eq!(
+        errors("%expect 0"),
+        vec![(
+            "reduce/reduce conflicts: 1 found, 0 expected".to_string(),
+            "%expect 0".to_string()
+        )]
+    );
+}
+
+#[test]
+fn three_way_reduce_reduce() {
+    // Like Bison, each reduction after the first on a token is a conflict
+    let input = "%expect-rr 1
+%token X
+%%
+s: a | b | c | X X ;
+a: X ;
+b: X ;
+c: X ;
+%%
+";
+    let built = build(input);
+    assert_eq!(built.tables.conflicts.len(), 1);
+    assert_eq!(built.tables.count(ConflictKind::ReduceReduce), 2);
+    let errors = check_conflicts(
+        &built.grammar,
+        &built.symbols,
+        &built.automaton,
+        &built.tables,
+    )
+    .into_iter()
+    .filter(|d| d.severity == Severity::Error)
+    .map(|d| (d.message, &input[d.span]))
+    .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![(
+            "reduce/reduce conflicts: 2 found, 1 expected".to_string(),
+            "%expect-rr 1"
+        )]
+    );
+    let input = input.replace("%expect-rr 1", "%expect-rr 2");
+    let built = build(&input);
+    assert!(check_conflicts(
+        &built.grammar,
+        &built.symbols,
+        &built.automaton,
+        &built.tables,
+    )
+    .is_empty());
+}
+
+#[test]
+fn negative_expect() {
+    // The lexer reads no negative numbers, but a grammar built otherwise can
+    // hold them
+    let input = "%expect 1
+%token X
+%%
+s: a | b | X X ;
+a: X ;
+b: X ;
+%%
+";
+    let mut built = build(input);
+    built.grammar.directives[0].data = Directive::ExpectRr { number: -1 };
+    let diagnostics = check_conflicts(
+        &built.grammar,
+        &built.symbols,
+        &built.automaton,
+        &built.tables,
+    )
+    .into_iter()
+    .map(|d| (d.severity, d.code, d.message, &input[d.span]))
+    .collect::<Vec<_>>();
+    assert_eq!(
+        diagnostics,
+        vec![
+            (
+                Severity::Error,
+                Some("conflicts-rr"),
+                "invalid %expect-rr count: -1".to_string(),
+                "%expect 1"
+            ),
+            (
+                Severity::Warning,
+                Some("conflicts-rr"),
+                "reduce/reduce conflict on token $end".to_string(),
+                "X"
+            ),
+        ]
+    );
+}
+
+const EXPRESSIONS: &str = "%token NUM
+%left '+' '-'
+%left '*'
+%%
+e: e '+' e | e '-' e | e '*' e | '(' e ')' | NUM ;
+%%
+";
+
+#[test]
+fn lr1_but_not_lalr() {
+    let input = "%%
+s: 'a' e 'c' | 'a' f 'd' | 'b' f 'c' | 'b' e 'd' ;
+e: 'e' ;
+f: 'e' ;
+%%
+";
+    let lalr = build_with(input, Mode::Lalr);
+    let canonical = build_with(input, Mode::Canonical);
+    let minimal = build_with(input, Mode::Minimal);
+    assert_eq!(lalr.tables.count(ConflictKind::ReduceReduce), 2);
+    assert_eq!(canonical.tables.conflicts, vec![]);
+    assert_eq!(minimal.tables.conflicts, vec![]);
+    assert_eq!(lalr.automaton.states.len(), 13);
+    assert_eq!(minimal.automaton.states.len(), 14);
+    assert_eq!(canonical.automaton.states.len(), 14);
+    for built in [&canonical, &minimal] {
+        assert_eq!(parse(built, "'a' 'e' 'c'"), Some(vec![5, 1]));
+        assert_eq!(parse(built, "'b' 'e' 'c'"), Some(vec![6, 3]));
+    }
+}
+
+#[test]
+fn modes_agree_on_lalr_grammars() {
+    let lalr = build_with(EXPRESSIONS, Mode::Lalr);
+    let canonical = build_with(EXPRESSIONS, Mode::Canonical);
+    let minimal = build_with(EXPRESSIONS, Mode::Minimal);
+    assert_eq!(minimal.automaton.states.len(), lalr.automaton.states.len());
+    assert!(canonical.automaton.states.len() > lalr.automaton.states.len());
+    for built in [&lalr, &canonical, &minimal] {
+        assert_eq!(built.tables.conflicts, vec![]);
+        assert_eq!(
+            parse(built, "'(' NUM '+' NUM ')' '*' NUM '-' NUM"),
+            Some(vec![5, 5, 1, 4, 5, 3, 5, 2])
+        );
+        assert!(parse(built, "'(' NUM '+' ')'").is_none());
+    }
+}
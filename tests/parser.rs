@@ -0,0 +1,177 @@
+// Syntax errors reported by the parser, and recovery from them.
+use yacc_parser::diagnostics::Severity;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::parser::Parser;
+use yacc_parser::token::Token;
+
+// The offending token and its text, the expected tokens and the message of
+// the first syntax error
+fn error(input: &str) -> (Token, &str, Vec<Token>, String) {
+    let err = Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap_err();
+    (
+        err.token.data,
+        &input[err.token.span],
+        err.expected,
+        err.message,
+    )
+}
+
+#[test]
+fn unknown_directive() {
+    assert_eq!(
+        error("%token A\n%foo B\n%%\na: A ;\n%%\n"),
+        (
+            Token::Directive,
+            "%foo",
+            vec![],
+            "Unknown directive '%foo'".to_string()
+        )
+    );
+}
+
+#[test]
+fn bad_prec() {
+    assert_eq!(
+        error("%%\na: A %left B ;\n%%\n"),
+        (
+            Token::Directive,
+            "%left",
+            vec![Token::Directive],
+            "Expected %prec, found %left".to_string()
+        )
+    );
+    assert_eq!(
+        error("%%\na: A %prec ;\n%%\n"),
+        (
+            Token::SemiColon,
+            ";",
+            vec![Token::Ident],
+            "Expected Ident, found SemiColon (;)".to_string()
+        )
+    );
+}
+
+#[test]
+fn unterminated_rule() {
+    assert_eq!(
+        error("%%\na: A | B"),
+        (
+            Token::Eof,
+            "",
+            vec![Token::Bar, Token::SemiColon],
+            "Expected Bar or SemiColon, found Eof ()".to_string()
+        )
+    );
+}
+
+#[test]
+fn missing_epilogue() {
+    assert_eq!(
+        error("%%\na: A ;\n"),
+        (
+            Token::Eof,
+            "",
+            vec![Token::Epilogue],
+            "Expected Epilogue, found Eof ()".to_string()
+        )
+    );
+}
+
+// The names of the rules which survive recovery, and the severity, message
+// and source text of each diagnostic
+fn recover(input: &str) -> (Vec<String>, Vec<(Severity, String, &str)>) {
+    let (grammar, diagnostics) = Parser::new(input, Lexer::new(input)).parse_grammar_recovering();
+    let rules = grammar.rules.into_iter().map(|r| r.name.data).collect();
+    let diagnostics = diagnostics
+        .into_iter()
+        .map(|d| (d.severity, d.message, &input[d.span]))
+        .collect();
+    (rules, diagnostics)
+}
+
+#[test]
+fn recovery() {
+    let input = "%token A B C
+%%
+a: A
+b: B ;
+| c: C ;
+d: A ; ; e: B ) C ;
+f: C ;
+:: =
+%%
+";
+    assert_eq!(
+        recover(input),
+        (
+            vec![
+                "b".to_string(),
+                "c".to_string(),
+                "d".to_string(),
+                "f".to_string()
+            ],
+            vec![
+                // The missing ';' is noticed at the name of the next rule,
+                // which is kept
+                (
+                    Severity::Error,
+                    "Expected Bar or SemiColon, found Ident (b)".to_string(),
+                    "b"
+                ),
+                // A stray token before a rule only loses the token
+                (
+                    Severity::Error,
+                    "Expected Ident or Epilogue, found Bar (|)".to_string(),
+                    "|"
+                ),
+                (
+                    Severity::Error,
+                    "Expected Ident or Epilogue, found SemiColon (;)".to_string(),
+                    ";"
+                ),
+                // A broken rule is dropped up to its ';'
+                (
+                    Severity::Error,
+                    "Expected Bar or SemiColon, found Err ())".to_string(),
+                    ")"
+                ),
+                // Junk before the second %% is skipped in one go
+                (
+                    Severity::Error,
+                    "Expected Ident or Epilogue, found Colon (:)".to_string(),
+                    ":"
+                ),
+            ]
+        )
+    );
+}
+
+#[test]
+fn recovery_at_eof() {
+    // Errors in the rules section do not hide a missing second %%
+    assert_eq!(
+        recover("%%\na: A B\nb: ;\nc: ) ;\n"),
+        (
+            vec!["b".to_string()],
+            vec![
+                (
+                    Severity::Error,
+                    "Expected Bar or SemiColon, found Ident (b)".to_string(),
+                    "b"
+                ),
+                (
+                    Severity::Error,
+                    "Expected Bar or SemiColon, found Err ())".to_string(),
+                    ")"
+                ),
+                (
+                    Severity::Error,
+                    "Expected Epilogue, found Eof ()".to_string(),
+                    ""
+                ),
+            ]
+        )
+    );
+}
@@ -0,0 +1,317 @@
+// Printing a grammar and parsing it again must give back the same grammar.
+use proptest::prelude::*;
+use yacc_parser::action::Action;
+use yacc_parser::format::format;
+use yacc_parser::format::FirstAlternative;
+use yacc_parser::format::FormatOptions;
+use yacc_parser::grammar::Alternative;
+use yacc_parser::grammar::Directive;
+use yacc_parser::grammar::Element;
+use yacc_parser::grammar::Grammar;
+use yacc_parser::grammar::Rule;
+use yacc_parser::grammar::TokenDeclaration;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::parser::Parser;
+use yacc_parser::token::Spanned;
+
+fn parse(input: &str) -> Grammar {
+    Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap_or_else(|err| panic!("{}\n{}", err, input))
+}
+
+fn spanned(data: String) -> Spanned<String> {
+    Spanned::new(data, 0..0)
+}
+
+// The grammar with every span set to 0..0 like the generated ones, so that
+// it compares equal to a grammar with the same structure parsed from
+// differently laid out source
+fn without_spans(mut grammar: Grammar) -> Grammar {
+    fn clear(spanned: &mut Spanned<String>) {
+        spanned.span = 0..0;
+    }
+    fn clear_action(action: &mut Action) {
+        *action = Action::new(spanned(action.code.data.clone()));
+    }
+    for directive in &mut grammar.directives {
+        directive.span = 0..0;
+        match &mut directive.data {
+            Directive::Type {
+                type_name,
+                rule_names,
+            } => {
+                clear(type_name);
+                rule_names.iter_mut().for_each(clear);
+            }
+            Directive::Token {
+                token_name,
+                rule_names,
+            } => {
+                token_name.iter_mut().for_each(clear);
+                for declaration in rule_names {
+                    clear(&mut declaration.name);
+                    declaration.alias.iter_mut().for_each(clear);
+                }
+            }
+            Directive::Left { rule_names }
+            | Directive::Right { rule_names }
+            | Directive::NonAssoc { rule_names } => rule_names.iter_mut().for_each(clear),
+            _ => {}
+        }
+    }
+    for rule in &mut grammar.rules {
+        rule.span = 0..0;
+        clear(&mut rule.name);
+        for alternative in &mut rule.alternatives {
+            alternative.span = 0..0;
+            for element in &mut alternative.elements {
+                match element {
+                    Element::Symbol(name) => clear(name),
+                    Element::Char { text, .. } | Element::String { text, .. } => clear(text),
+                    Element::Action(action) => clear_action(action),
+                }
+            }
+            alternative.precedence.iter_mut().for_each(clear);
+            alternative.action.iter_mut().for_each(clear_action);
+        }
+    }
+    grammar.prologues.iter_mut().for_each(clear);
+    clear(&mut grammar.epilogue);
+    grammar
+}
+
+fn ident() -> impl Strategy<Value = String> {
+    "[a-zA-Z][a-zA-Z0-9_]{0,8}"
+}
+
+fn char_literal() -> impl Strategy<Value = (String, char)> {
+    prop_oneof![
+        "[-+*/()\\[\\],;:|<>=a-z0-9\"]"
+            .prop_map(|c| (format!("'{}'", c), c.chars().next().unwrap())),
+        Just(("'\\n'".to_string(), '\n')),
+        Just(("'\\''".to_string(), '\'')),
+        Just(("'\\\\'".to_string(), '\\')),
+        (0..256u32).prop_map(|v| (format!("'\\{:o}'", v), char::from_u32(v).unwrap())),
+        (0..256u32).prop_map(|v| (format!("'\\x{:x}'", v), char::from_u32(v).unwrap())),
+    ]
+}
+
+// A string literal, with escapes, and its value
+fn string_literal() -> impl Strategy<Value = (String, String)> {
+    let piece = prop_oneof![
+        "[a-z +;|:%{}']".prop_map(|c| (c.clone(), c)),
+        Just(("\\\"".to_string(), "\"".to_string())),
+        Just(("\\\\".to_string(), "\\".to_string())),
+        Just(("\\n".to_string(), "\n".to_string())),
+        Just(("\\101".to_string(), "A".to_string())),
+    ];
+    prop::collection::vec(piece, 0..6).prop_map(|pieces| {
+        let text = pieces.iter().map(|(t, _)| t.as_str()).collect::<String>();
+        let value = pieces.iter().map(|(_, v)| v.as_str()).collect::<String>();
+        (format!("\"{}\"", text), value)
+    })
+}
+
+fn token_declaration() -> impl Strategy<Value = TokenDeclaration> {
+    (
+        symbol(),
+        prop::option::of(string_literal().prop_map(|(text, _)| spanned(text))),
+    )
+        .prop_map(|(name, alias)| TokenDeclaration { name, alias })
+}
+
+fn symbol() -> impl Strategy<Value = Spanned<String>> {
+    prop_oneof![3 => ident(), 1 => char_literal().prop_map(|(text, _)| text)].prop_map(spanned)
+}
+
+fn element() -> impl Strategy<Value = Element> {
+    prop_oneof![
+        3 => ident().prop_map(|name| Element::Symbol(spanned(name))),
+        1 => char_literal().prop_map(|(text, value)| Element::Char {
+            text: spanned(text),
+            value,
+        }),
+        1 => string_literal().prop_map(|(text, value)| Element::String {
+            text: spanned(text),
+            value,
+        }),
+        1 => code().prop_map(|c| Element::Action(Action::new(spanned(format!("{{{}}}", c))))),
+    ]
+}
+
+fn type_name() -> impl Strategy<Value = Spanned<String>> {
+    "[a-z][a-z0-9_]{0,6}".prop_map(|t| spanned(format!("<{}>", t)))
+}
+
+// Code without braces, with balanced ones, or with unbalanced ones hidden in
+// C literals and comments
+fn code() -> impl Strategy<Value = String> {
+    let text = "[ a-z0-9;=$*+\n]{0,20}";
+    prop_oneof![
+        text.prop_map(String::from),
+        (text, text, text).prop_map(|(a, b, c)| format!("{}{{{}}}{}", a, b, c)),
+        (text, text).prop_map(|(a, b)| format!("{}'}}' \"{{\\\"\" /* }} %}} */{}", a, b)),
+    ]
+}
+
+fn directive() -> impl Strategy<Value = Spanned<Directive>> {
+    let symbols = || prop::collection::vec(symbol(), 0..5);
+    prop_oneof![
+        Just(Directive::PureParser),
+        (0..1000i32).prop_map(|number| Directive::Expect { number }),
+        (0..1000i32).prop_map(|number| Directive::ExpectRr { number }),
+        "[a-z_]{1,8}".prop_map(|prefix| Directive::NamePrefix { prefix }),
+        Just(Directive::Locations),
+        code().prop_map(|params| Directive::ParseParam { params }),
+        code().prop_map(|params| Directive::LexProgram { params }),
+        code().prop_map(|code| Directive::Union { code }),
+        (
+            type_name(),
+            prop::collection::vec(ident().prop_map(spanned), 0..5)
+        )
+            .prop_map(|(type_name, rule_names)| Directive::Type {
+                type_name,
+                rule_names,
+            }),
+        (
+            prop::option::of(type_name()),
+            prop::collection::vec(token_declaration(), 0..5)
+        )
+            .prop_map(|(token_name, rule_names)| {
+                Directive::Token {
+                    token_name,
+                    rule_names,
+                }
+            }),
+        symbols().prop_map(|rule_names| Directive::Left { rule_names }),
+        symbols().prop_map(|rule_names| Directive::Right { rule_names }),
+        symbols().prop_map(|rule_names| Directive::NonAssoc { rule_names }),
+    ]
+    .prop_map(|d| Spanned::new(d, 0..0))
+}
+
+fn alternative() -> impl Strategy<Value = Alternative> {
+    (
+        prop::collection::vec(element(), 0..6),
+        prop::option::of(ident().prop_map(spanned)),
+        prop::option::of(code().prop_map(|c| Action::new(spanned(format!("{{{}}}", c))))),
+    )
+        .prop_map(|(mut elements, precedence, mut action)| {
+            // A trailing action is the alternative's action
+            if action.is_none() {
+                if let Some(Element::Action(last)) = elements.last() {
+                    action = Some(last.clone());
+                    elements.pop();
+                }
+            }
+            Alternative {
+                elements,
+                precedence,
+                action,
+                span: 0..0,
+            }
+        })
+}
+
+fn rule() -> impl Strategy<Value = Rule> {
+    (ident(), prop::collection::vec(alternative(), 1..5)).prop_map(|(name, alternatives)| Rule {
+        name: spanned(name),
+        alternatives,
+        span: 0..0,
+    })
+}
+
+fn grammar() -> impl Strategy<Value = Grammar> {
+    (
+        prop::collection::vec(directive(), 0..8),
+        prop::collection::vec("[^%'\"/]{0,30}".prop_map(spanned), 0..3),
+        prop::collection::vec(rule(), 0..6),
+        ".{0,30}(\n.{0,30}){0,3}".prop_map(spanned),
+    )
+        .prop_map(|(directives, prologues, rules, epilogue)| Grammar {
+            directives,
+            rules,
+            prologues,
+            epilogue,
+        })
+}
+
+fn format_options() -> impl Strategy<Value = FormatOptions> {
+    (
+        0..8usize,
+        any::<bool>(),
+        prop_oneof![
+            Just(FirstAlternative::Colon),
+            Just(FirstAlternative::SameLine),
+            Just(FirstAlternative::Bar)
+        ],
+        prop_oneof![Just(0usize), 10..120usize],
+    )
+        .prop_map(
+            |(indent, align_actions, first_alternative, max_width)| FormatOptions {
+                indent,
+                align_actions,
+                first_alternative,
+                max_width,
+                preserve_comments: true,
+            },
+        )
+}
+
+proptest! {
+    #[test]
+    fn display_roundtrip(grammar in grammar()) {
+        let printed = grammar.to_string();
+        prop_assert_eq!(without_spans(parse(&printed)), grammar);
+    }
+
+    #[test]
+    fn display_reparse_is_stable(grammar in grammar()) {
+        let first = parse(&grammar.to_string());
+        let second = parse(&first.to_string());
+        prop_assert_eq!(second, first);
+    }
+
+    #[test]
+    fn format_roundtrip(grammar in grammar(), options in format_options()) {
+        let formatted = format(&grammar, &options);
+        prop_assert_eq!(without_spans(parse(&formatted)), grammar);
+    }
+}
+
+#[test]
+fn display_roundtrip_example() {
+    let input = r#"%{
+#include <stdio.h>
+%}
+%pure-parser
+%expect 2
+%name-prefix="calc_"
+%locations
+%parse-param { void *scanner }
+%union { int ival; }
+%token <ival> NUM "number"
+%token PLUS "+" ESCAPED "a\"b"
+%type <ival> expr
+%left '+' '-'
+%right UMINUS
+%%
+input: expr { printf("%d\n", $1); } ;
+expr: expr '+' expr { $$ = $1 + $3; }
+    | expr "+" expr { $$ = $1 + $3; }
+    | '\'' expr '\x41'
+    | expr { mid($1); } '*' { $$ = $2; } expr { $$ = $1 * $5; }
+    | '(' { enter(); } expr ')' %prec UMINUS { $$ = $3; }
+    | '-' expr %prec UMINUS { $$ = -$2; }
+    |
+    | NUM
+    ;
+%%
+int main() { return 0; }
+"#;
+    let grammar = parse(input);
+    let printed = grammar.to_string();
+    assert_eq!(without_spans(parse(&printed)), without_spans(grammar));
+    assert_eq!(parse(&printed).to_string(), printed);
+}
@@ -0,0 +1,123 @@
+// Resolution of the names in a grammar to symbols.
+use yacc_parser::diagnostics::Severity;
+use yacc_parser::lexer::Lexer;
+use yacc_parser::parser::Parser;
+use yacc_parser::symbols::Associativity;
+use yacc_parser::symbols::Precedence;
+use yacc_parser::symbols::SymbolKind;
+use yacc_parser::symbols::SymbolTable;
+
+fn table(input: &str) -> (SymbolTable, Vec<(Severity, String, &str)>) {
+    let grammar = Parser::new(input, Lexer::new(input))
+        .parse_grammar()
+        .unwrap();
+    let (table, diagnostics) = SymbolTable::new(&grammar);
+    let diagnostics = diagnostics
+        .into_iter()
+        .map(|d| (d.severity, d.message, &input[d.span]))
+        .collect();
+    (table, diagnostics)
+}
+
+#[test]
+fn classifies_symbols() {
+    let input = "%token <ival> NUM PLUS \"+\"
+%left PLUS '-'
+%right '^'
+%type <ival> expr
+%%
+expr: expr \"+\" expr | expr '-' expr | expr '^' expr | term ;
+term: NUM | '(' expr ')' ;
+%%
+";
+    let (table, diagnostics) = table(input);
+    assert_eq!(diagnostics, vec![]);
+    let names = table
+        .symbols()
+        .map(|(_, s)| s.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec!["$end", "error", "NUM", "PLUS", "'-'", "'^'", "'('", "')'", "expr", "term"]
+    );
+
+    let plus = table.symbol(table.get("\"+\"").unwrap());
+    assert_eq!(plus.name, "PLUS");
+    assert_eq!(plus.kind, SymbolKind::Terminal);
+    assert_eq!(plus.type_name.as_deref(), Some("<ival>"));
+    assert_eq!(
+        plus.precedence,
+        Some(Precedence {
+            level: 1,
+            associativity: Associativity::Left,
+        })
+    );
+
+    let power = table.symbol(table.get("'^'").unwrap());
+    assert_eq!(power.kind, SymbolKind::Char('^'));
+    assert_eq!(power.precedence.unwrap().level, 2);
+    assert_eq!(
+        power.precedence.unwrap().associativity,
+        Associativity::Right
+    );
+
+    let expr = table.get("expr").unwrap();
+    assert_eq!(table.start(), Some(expr));
+    assert_eq!(table.symbol(expr).kind, SymbolKind::Nonterminal);
+    assert_eq!(table.symbol(expr).type_name.as_deref(), Some("<ival>"));
+    assert_eq!(table.symbol(expr).rules, vec![0]);
+    assert_eq!(table.terminals().count(), 8);
+    assert_eq!(table.nonterminals().count(), 2);
+}
+
+#[test]
+fn undefined_and_redefined_symbols() {
+    let input = "%token NUM
+%token NUM
+%left '+'
+%left '+'
+%type <a> expr
+%type <b> expr
+%%
+expr: NUM '+' term | undefined ;
+NUM: expr ;
+%%
+";
+    let (_, diagnostics) = table(input);
+    assert_eq!(
+        diagnostics,
+        vec![
+            (
+                Severity::Warning,
+                "symbol NUM redeclared".to_string(),
+                "NUM"
+            ),
+            (
+                Severity::Error,
+                "%left redeclaration for '+'".to_string(),
+                "'+'"
+            ),
+            (
+                Severity::Error,
+                "rule given for NUM, which is a token".to_string(),
+                "NUM"
+            ),
+            (
+                Severity::Error,
+                "symbol term is used, but is not defined as a token and has no rules".to_string(),
+                "term"
+            ),
+            (
+                Severity::Error,
+                "symbol undefined is used, but is not defined as a token and has no rules"
+                    .to_string(),
+                "undefined"
+            ),
+            (
+                Severity::Error,
+                "type redeclaration for expr".to_string(),
+                "expr"
+            ),
+        ]
+    );
+}